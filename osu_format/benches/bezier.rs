@@ -0,0 +1,21 @@
+#![feature(test)]
+extern crate test;
+extern crate rand;
+extern crate osu_format;
+
+use test::Bencher;
+use osu_format::bezier::Bezier;
+
+#[bench]
+fn bench_interpolation(b: &mut Bencher) {
+    b.iter(|| {
+        let mut spline = Bezier::new();
+
+        //here approx length for curve will be about 1000
+        for i in 0..10 {
+            spline.add_vertexf( (i as f32 * 100.0, rand::random::<f32>() * 100.0) );
+        }
+
+        spline.get_tesselated_points();
+    });
+}
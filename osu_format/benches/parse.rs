@@ -0,0 +1,39 @@
+#![feature(test)]
+extern crate test;
+extern crate osu_format;
+
+use test::Bencher;
+use osu_format::{Beatmap, FileFormat};
+
+const MAP: &'static str = include_str!("../tests/test.osu");
+
+//Whole file is split into lines first
+#[bench]
+fn bench_from_osu(b: &mut Bencher) {
+    b.iter(|| {
+        let mut lines = MAP.lines().collect();
+        let mut map = Beatmap::new();
+        map.from_osu(&mut lines).unwrap();
+        map
+    });
+}
+
+//Line and field buffers are reused, values kept in map are still allocated
+#[bench]
+fn bench_from_reader(b: &mut Bencher) {
+    b.iter(|| {
+        let mut map = Beatmap::new();
+        map.from_reader(MAP.as_bytes()).unwrap();
+        map
+    });
+}
+
+//Stops at [HitObjects]
+#[bench]
+fn bench_metadata_from_reader(b: &mut Bencher) {
+    b.iter(|| {
+        let mut map = Beatmap::new();
+        map.metadata_from_reader(MAP.as_bytes()).unwrap();
+        map
+    });
+}
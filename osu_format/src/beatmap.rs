@@ -0,0 +1,2223 @@
+use std::slice;
+use std::io::BufRead;
+use hit_object::*;
+use error::*;
+use event::*;
+use storyboard::Storyboard;
+use stacking::apply_stacking;
+use difficulty::DifficultyAttributes;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SampleSet {
+    NONE,
+    NORMAL,
+    SOFT,
+    DRUM
+}
+
+impl Default for SampleSet {
+    fn default() -> SampleSet { SampleSet::NONE }
+}
+
+impl SampleSet {
+    //Unknown values mean "not set"
+    pub fn from_index(index: i32) -> SampleSet {
+        match index {
+            1 => { SampleSet::NORMAL }
+            2 => { SampleSet::SOFT }
+            3 => { SampleSet::DRUM }
+            _ => { SampleSet::NONE }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SampleSet::NONE => "None",
+            SampleSet::NORMAL => "Normal",
+            SampleSet::SOFT => "Soft",
+            SampleSet::DRUM => "Drum",
+        }
+    }
+
+    //Numeric value used in timing points and hit samples
+    pub fn index(&self) -> i32 {
+        match *self {
+            SampleSet::NONE => 0,
+            SampleSet::NORMAL => 1,
+            SampleSet::SOFT => 2,
+            SampleSet::DRUM => 3,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameMode {
+    Osu,
+    Taiko,
+    CatchTheBeat,
+    Mania
+}
+
+impl Default for GameMode {
+    fn default() -> GameMode { GameMode::Osu }
+}
+
+impl GameMode {
+    pub fn from_index(index: i32) -> Option<GameMode> {
+        match index {
+            0 => Some(GameMode::Osu),
+            1 => Some(GameMode::Taiko),
+            2 => Some(GameMode::CatchTheBeat),
+            3 => Some(GameMode::Mania),
+            _ => None
+        }
+    }
+
+    pub fn index(&self) -> i32 {
+        match *self {
+            GameMode::Osu => 0,
+            GameMode::Taiko => 1,
+            GameMode::CatchTheBeat => 2,
+            GameMode::Mania => 3,
+        }
+    }
+}
+
+//Used when map has no [Colours] section
+pub const DEFAULT_COMBO_COLORS: [(i32, i32, i32, f32); 4] = [
+    (255, 192, 0, 1.0),
+    (0, 202, 0, 1.0),
+    (18, 124, 255, 1.0),
+    (242, 24, 57, 1.0),
+];
+
+pub const FORMAT_HEADER: &'static str = "osu file format v";
+//Version written by to_osu
+pub const FORMAT_VERSION: i32 = 14;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SectionType {
+    General,
+    Editor,
+    Metadata,
+    Difficulty,
+    Events,
+    TimingPoints,
+    Colours,
+    HitObjects
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TimingPoint {
+    inherited: bool,
+
+    time_start: i32,
+    //Length of beat in ms for non-inherited
+    beat_length: f32,
+    //slider multiplier for inherited, negative percent of base velocity inverse: -50 is x2
+    velocity: f32,
+    //beats per measure
+    meter: i32,
+
+    sample_type: SampleSet,
+    sample_type_custom: i32,
+    //sample volume 0 to 100
+    sample_volume: i32,
+
+    //kiai mode
+    kiai: bool
+}
+
+impl TimingPoint {
+    //Red line with beat length in ms
+    pub fn new_uninherited(time_start: i32, beat_length: f32, meter: i32) -> TimingPoint {
+        TimingPoint {
+            inherited: false,
+            time_start: time_start,
+            beat_length: beat_length,
+            meter: meter,
+            sample_volume: 100,
+            ..Default::default()
+        }
+    }
+
+    //Green line making sliders x multiplier faster, as -50 in .osu for 2.0
+    pub fn new_inherited(time_start: i32, velocity_multiplier: f32) -> TimingPoint {
+        TimingPoint {
+            inherited: true,
+            time_start: time_start,
+            velocity: -100.0 / velocity_multiplier,
+            meter: 4,
+            sample_volume: 100,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_samples(mut self, sample_set: SampleSet, sample_index: i32, sample_volume: i32) -> TimingPoint {
+        self.sample_type = sample_set;
+        self.sample_type_custom = sample_index;
+        self.sample_volume = sample_volume;
+        self
+    }
+
+    pub fn with_kiai(mut self, kiai: bool) -> TimingPoint {
+        self.kiai = kiai;
+        self
+    }
+
+    pub fn time_start(&self) -> i32 {
+        self.time_start
+    }
+
+    //Green line in editor
+    pub fn inherited(&self) -> bool {
+        self.inherited
+    }
+
+    //ms per beat, 0 for inherited points
+    pub fn beat_length(&self) -> f32 {
+        self.beat_length
+    }
+
+    //As written in .osu for inherited points, 0 for non-inherited
+    pub fn velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    pub fn meter(&self) -> i32 {
+        self.meter
+    }
+
+    pub fn sample_set(&self) -> SampleSet {
+        self.sample_type
+    }
+
+    //Custom sample index, 0 is default skin samples
+    pub fn sample_index(&self) -> i32 {
+        self.sample_type_custom
+    }
+
+    pub fn sample_volume(&self) -> i32 {
+        self.sample_volume
+    }
+
+    pub fn kiai(&self) -> bool {
+        self.kiai
+    }
+
+    //Multiplier of beat length for sliders: 0.5 means slider takes half of time, so it's x2 faster.
+    //1.0 for non-inherited points
+    pub fn get_slider_multiplier(&self) -> f32 {
+        if self.inherited {
+            (self.velocity / -100.0).max(0.1).min(10.0)
+        } else {
+            1.0
+        }
+    }
+
+    pub fn to_osu(&self) -> String {
+        format!("{},{},{},{},{},{},{},{}",
+            self.time_start,
+            if self.inherited { self.velocity } else { self.beat_length },
+            self.meter,
+            self.sample_type.index(),
+            self.sample_type_custom,
+            self.sample_volume,
+            if self.inherited { 0 } else { 1 },
+            if self.kiai { 1 } else { 0 }
+        )
+    }
+}
+
+//Summary of map shown in song select, computed when map is loaded
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct BeatmapStat {
+    circles: i32,
+    sliders: i32,
+    spinners: i32,
+    holds: i32,
+    min_bpm: f32,
+    max_bpm: f32,
+    //BPM lasting for the longest time
+    dominant_bpm: f32,
+    //ms from song start to end of last object
+    total_length: i32,
+    //ms from first object to end of last one without breaks
+    drain_time: i32,
+    max_combo: i32,
+}
+
+impl BeatmapStat {
+    pub fn circles(&self) -> i32 {
+        self.circles
+    }
+
+    pub fn sliders(&self) -> i32 {
+        self.sliders
+    }
+
+    pub fn spinners(&self) -> i32 {
+        self.spinners
+    }
+
+    pub fn holds(&self) -> i32 {
+        self.holds
+    }
+
+    pub fn objects(&self) -> i32 {
+        self.circles + self.sliders + self.spinners + self.holds
+    }
+
+    pub fn min_bpm(&self) -> f32 {
+        self.min_bpm
+    }
+
+    pub fn max_bpm(&self) -> f32 {
+        self.max_bpm
+    }
+
+    pub fn dominant_bpm(&self) -> f32 {
+        self.dominant_bpm
+    }
+
+    pub fn total_length(&self) -> i32 {
+        self.total_length
+    }
+
+    pub fn drain_time(&self) -> i32 {
+        self.drain_time
+    }
+
+    pub fn max_combo(&self) -> i32 {
+        self.max_combo
+    }
+}
+
+//Map is read into existing value, so from_* take &mut self
+#[allow(clippy::wrong_self_convention)]
+pub trait FileFormat {
+    fn from_osu(&mut self, lines: &mut Vec<&str>) -> Result<(), BeatmapParseError>;
+    //Skips bad lines and returns them as warnings
+    fn from_osu_lenient(&mut self, lines: &mut Vec<&str>) -> Result<Vec<BeatmapParseError>, BeatmapParseError>;
+    //Always writes FORMAT_VERSION, whatever version was read
+    fn to_osu(&self) -> String;
+}
+
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct Beatmap {
+    //Main data
+    format_version: i32,
+    id: i32,
+    objects: Vec<HitObject>,
+    timing_points: Vec<TimingPoint>,
+    //indexes in timing_points by kind, for lookups by time
+    uninherited_points: Vec<usize>,
+    inherited_points: Vec<usize>,
+    events: Vec<Event>,
+    storyboard: Storyboard,
+    //storyboard of the set from .osb, drawn below the one from [Events] and never written to .osu
+    osb_storyboard: Storyboard,
+
+    //general data
+    audio_filename: String,
+    mode: GameMode,
+    skin_preference: String,
+
+    //parameters
+    stack_leniency: f32,
+    beatmap_set_id: i32,
+    letterbox_in_breaks: bool,
+    widescreen_storyboard: bool,
+    epilepsy_warning: bool,
+    special_style: bool,
+    use_skin_sprites: bool,
+    story_fire_in_front: bool,
+    samples_match_playback_rate: bool,
+
+    audio_lead_in: i32,
+    preview_time: i32,
+    countdown_type: i32,
+    sample_set: SampleSet,
+
+    //difficulty
+    hp_drain: f32,
+    circle_size: f32,
+    overall_difficulty: f32,
+    approach_rate: f32,
+    slider_multiplier: f32,
+    slider_tick_rate: f32,
+
+    //meta data
+    title: String,
+    title_unicode: String,
+    artist: String,
+    artist_unicode: String,
+    creator: String,
+    difficulty: String,
+    source: String,
+    tags: Vec<String>,
+
+    //RGBA format
+    colors: Vec<(i32, i32, i32, f32)>,
+
+    stat: BeatmapStat,
+
+
+    //editor related
+    //sorted offsets in ms
+    bookmarks: Vec<i32>,
+    distance_spacing: f32,
+    beat_divisor: i32,
+    grid_size: i32,
+    timeline_zoom: f32,
+}
+
+impl Beatmap {
+    pub fn new() -> Self {
+        Beatmap {
+            distance_spacing: 1.0,
+            beat_divisor: 4,
+            grid_size: 4,
+            timeline_zoom: 1.0,
+            story_fire_in_front: true,
+            stack_leniency: 0.7,
+            hp_drain: 5.0,
+            circle_size: 5.0,
+            overall_difficulty: 5.0,
+            approach_rate: 5.0,
+            slider_multiplier: 1.4,
+            slider_tick_rate: 1.0,
+            ..Default::default()
+        }
+    }
+
+    //Version from header of file
+    pub fn format_version(&self) -> i32 {
+        self.format_version
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn beatmap_set_id(&self) -> i32 {
+        self.beatmap_set_id
+    }
+
+    pub fn audio_filename(&self) -> &str {
+        &self.audio_filename
+    }
+
+    pub fn mode(&self) -> GameMode {
+        self.mode
+    }
+
+    pub fn skin_preference(&self) -> &str {
+        &self.skin_preference
+    }
+
+    //ms of silence added before the song
+    pub fn audio_lead_in(&self) -> i32 {
+        self.audio_lead_in
+    }
+
+    //ms of song played in song select
+    pub fn preview_time(&self) -> i32 {
+        self.preview_time
+    }
+
+    //0 is no countdown, 1 normal, 2 half and 3 double speed
+    pub fn countdown_type(&self) -> i32 {
+        self.countdown_type
+    }
+
+    //SampleSet from [General], used where timing points don't set it
+    pub fn sample_set(&self) -> SampleSet {
+        self.sample_set
+    }
+
+    pub fn stack_leniency(&self) -> f32 {
+        self.stack_leniency
+    }
+
+    pub fn letterbox_in_breaks(&self) -> bool {
+        self.letterbox_in_breaks
+    }
+
+    pub fn widescreen_storyboard(&self) -> bool {
+        self.widescreen_storyboard
+    }
+
+    pub fn epilepsy_warning(&self) -> bool {
+        self.epilepsy_warning
+    }
+
+    pub fn special_style(&self) -> bool {
+        self.special_style
+    }
+
+    pub fn use_skin_sprites(&self) -> bool {
+        self.use_skin_sprites
+    }
+
+    pub fn story_fire_in_front(&self) -> bool {
+        self.story_fire_in_front
+    }
+
+    pub fn samples_match_playback_rate(&self) -> bool {
+        self.samples_match_playback_rate
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    //Falls back to romanised title if there is no unicode one
+    pub fn title_unicode(&self) -> &str {
+        if self.title_unicode.is_empty() { &self.title } else { &self.title_unicode }
+    }
+
+    pub fn artist(&self) -> &str {
+        &self.artist
+    }
+
+    pub fn artist_unicode(&self) -> &str {
+        if self.artist_unicode.is_empty() { &self.artist } else { &self.artist_unicode }
+    }
+
+    pub fn creator(&self) -> &str {
+        &self.creator
+    }
+
+    //Difficulty name, "Version" in .osu
+    pub fn version(&self) -> &str {
+        &self.difficulty
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn hp_drain(&self) -> f32 {
+        self.hp_drain
+    }
+
+    pub fn circle_size(&self) -> f32 {
+        self.circle_size
+    }
+
+    pub fn overall_difficulty(&self) -> f32 {
+        self.overall_difficulty
+    }
+
+    pub fn approach_rate(&self) -> f32 {
+        self.approach_rate
+    }
+
+    //Hundreds of osu!pixels slider travels per beat
+    pub fn slider_multiplier(&self) -> f32 {
+        self.slider_multiplier
+    }
+
+    //Slider ticks per beat
+    pub fn slider_tick_rate(&self) -> f32 {
+        self.slider_tick_rate
+    }
+
+    //Combo colours as set in [Colours], empty if map uses skin ones
+    pub fn colors(&self) -> &Vec<(i32, i32, i32, f32)> {
+        &self.colors
+    }
+
+    //Colour of object's combo, falls back to DEFAULT_COMBO_COLORS
+    pub fn combo_colour(&self, obj: &HitObject) -> (i32, i32, i32, f32) {
+        self.colour_at_index(obj.combo_index)
+    }
+
+    //Index wraps around colours, negative ones take the first
+    fn colour_at_index(&self, combo_index: i32) -> (i32, i32, i32, f32) {
+        let colors:&[(i32, i32, i32, f32)] = if self.colors.is_empty() { &DEFAULT_COMBO_COLORS } else { &self.colors };
+        colors[combo_index.max(0) as usize % colors.len()]
+    }
+
+    pub fn bookmarks(&self) -> &Vec<i32> {
+        &self.bookmarks
+    }
+
+    //Bookmarks in [start, end] range
+    pub fn bookmarks_between(&self, start: i32, end: i32) -> &[i32] {
+        let from = match self.bookmarks.binary_search(&start) {
+            Ok(mut i) => {
+                while i > 0 && self.bookmarks[i - 1] == start { i -= 1; }
+                i
+            }
+            Err(i) => i
+        };
+        let to = match self.bookmarks.binary_search(&end) {
+            Ok(mut i) => {
+                while i + 1 < self.bookmarks.len() && self.bookmarks[i + 1] == end { i += 1; }
+                i + 1
+            }
+            Err(i) => i
+        };
+        if from < to { &self.bookmarks[from..to] } else { &self.bookmarks[0..0] }
+    }
+
+    pub fn distance_spacing(&self) -> f32 {
+        self.distance_spacing
+    }
+
+    pub fn beat_divisor(&self) -> i32 {
+        self.beat_divisor
+    }
+
+    pub fn grid_size(&self) -> i32 {
+        self.grid_size
+    }
+
+    pub fn timeline_zoom(&self) -> f32 {
+        self.timeline_zoom
+    }
+
+    pub fn events(&self) -> &Vec<Event> {
+        &self.events
+    }
+
+    pub fn background(&self) -> Option<&Event> {
+        self.events.iter().find(|e| match **e { Event::Background { .. } => true, _ => false })
+    }
+
+    pub fn video(&self) -> Option<&Event> {
+        self.events.iter().find(|e| match **e { Event::Video { .. } => true, _ => false })
+    }
+
+    //(start, end) of every break in ms
+    pub fn breaks(&self) -> Vec<(i32, i32)> {
+        self.events.iter().filter_map(|e| match *e {
+            Event::Break { start_time, end_time } => Some( (start_time, end_time) ),
+            _ => None
+        }).collect()
+    }
+
+    //Used for pausing HP drain
+    pub fn is_break(&self, time: i32) -> bool {
+        self.events.iter().any(|e| match *e {
+            Event::Break { start_time, end_time } => time >= start_time && time <= end_time,
+            _ => false
+        })
+    }
+
+    //Storyboard from [Events] section
+    pub fn storyboard(&self) -> &Storyboard {
+        &self.storyboard
+    }
+
+    pub fn osb_storyboard(&self) -> &Storyboard {
+        &self.osb_storyboard
+    }
+
+    //Name of .osb file shared by all difficulties of the set
+    pub fn osb_filename(&self) -> String {
+        format!("{} - {} ({}).osb", self.artist, self.title, self.creator)
+    }
+
+    //Replaces storyboard from .osb file, the one from [Events] section is kept apart
+    pub fn load_osb(&mut self, lines: &mut Vec<&str>) -> Result<(), BeatmapParseError> {
+        let mut osb = Storyboard::new();
+        try!(osb.from_osb(lines));
+        self.osb_storyboard = osb;
+        Ok(())
+    }
+
+    //Whether gameplay should be letterboxed at time
+    pub fn letterbox_at(&self, time: i32) -> bool {
+        self.letterbox_in_breaks && self.is_break(time)
+    }
+}
+
+/*
+  Map made in code instead of .osu text, for tools and tests.
+  Objects and timing points may be added in any order, build() sorts them
+  and computes everything as from_osu does.
+*/
+pub struct BeatmapBuilder {
+    map: Beatmap
+}
+
+impl Default for BeatmapBuilder {
+    fn default() -> BeatmapBuilder {
+        BeatmapBuilder::new()
+    }
+}
+
+impl BeatmapBuilder {
+    pub fn new() -> BeatmapBuilder {
+        BeatmapBuilder { map: Beatmap { format_version: FORMAT_VERSION, ..Beatmap::new() } }
+    }
+
+    pub fn mode(mut self, mode: GameMode) -> BeatmapBuilder {
+        self.map.mode = mode;
+        self
+    }
+
+    pub fn audio_filename(mut self, audio_filename: &str) -> BeatmapBuilder {
+        self.map.audio_filename = audio_filename.to_string();
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> BeatmapBuilder {
+        self.map.title = title.to_string();
+        self
+    }
+
+    pub fn artist(mut self, artist: &str) -> BeatmapBuilder {
+        self.map.artist = artist.to_string();
+        self
+    }
+
+    pub fn creator(mut self, creator: &str) -> BeatmapBuilder {
+        self.map.creator = creator.to_string();
+        self
+    }
+
+    pub fn version(mut self, version: &str) -> BeatmapBuilder {
+        self.map.difficulty = version.to_string();
+        self
+    }
+
+    //CS, AR, OD and HP, clock rate is ignored
+    pub fn difficulty(mut self, attrs: &DifficultyAttributes) -> BeatmapBuilder {
+        self.map.circle_size = attrs.circle_size;
+        self.map.approach_rate = attrs.approach_rate;
+        self.map.overall_difficulty = attrs.overall_difficulty;
+        self.map.hp_drain = attrs.hp_drain;
+        self
+    }
+
+    pub fn slider_multiplier(mut self, slider_multiplier: f32) -> BeatmapBuilder {
+        self.map.slider_multiplier = slider_multiplier;
+        self
+    }
+
+    pub fn slider_tick_rate(mut self, slider_tick_rate: f32) -> BeatmapBuilder {
+        self.map.slider_tick_rate = slider_tick_rate;
+        self
+    }
+
+    pub fn stack_leniency(mut self, stack_leniency: f32) -> BeatmapBuilder {
+        self.map.stack_leniency = stack_leniency;
+        self
+    }
+
+    pub fn timing_point(mut self, point: TimingPoint) -> BeatmapBuilder {
+        self.map.timing_points.push(point);
+        self
+    }
+
+    pub fn object(mut self, object: HitObject) -> BeatmapBuilder {
+        self.map.objects.push(object);
+        self
+    }
+
+    pub fn break_period(mut self, start_time: i32, end_time: i32) -> BeatmapBuilder {
+        self.map.events.push(Event::Break { start_time: start_time, end_time: end_time });
+        self
+    }
+
+    pub fn build(self) -> Beatmap {
+        let mut map = self.map;
+        map.post_process();
+        map
+    }
+}
+
+impl FileFormat for Beatmap {
+    fn from_osu(&mut self, lines: &mut Vec<&str>) -> Result<(), BeatmapParseError> {
+        self.parse_lines(lines, false).map(|_| ())
+    }
+
+    fn from_osu_lenient(&mut self, lines: &mut Vec<&str>) -> Result<Vec<BeatmapParseError>, BeatmapParseError> {
+        self.parse_lines(lines, true)
+    }
+
+    fn to_osu(&self) -> String {
+        let mut out:Vec<String> = Vec::new();
+        out.push(format!("{}{}", FORMAT_HEADER, FORMAT_VERSION));
+        out.push(String::new());
+
+        out.push("[General]".to_string());
+        out.push(format!("AudioFilename: {}", self.audio_filename));
+        out.push(format!("AudioLeadIn: {}", self.audio_lead_in));
+        out.push(format!("PreviewTime: {}", self.preview_time));
+        out.push(format!("Countdown: {}", self.countdown_type));
+        out.push(format!("SampleSet: {}", self.sample_set.name()));
+        out.push(format!("StackLeniency: {}", self.stack_leniency));
+        out.push(format!("Mode: {}", self.mode.index()));
+        out.push(format!("LetterboxInBreaks: {}", flag(self.letterbox_in_breaks)));
+        if !self.skin_preference.is_empty() {
+            out.push(format!("SkinPreference:{}", self.skin_preference));
+        }
+        out.push(format!("SpecialStyle: {}", flag(self.special_style)));
+        out.push(format!("UseSkinSprites: {}", flag(self.use_skin_sprites)));
+        out.push(format!("StoryFireInFront: {}", flag(self.story_fire_in_front)));
+        out.push(format!("EpilepsyWarning: {}", flag(self.epilepsy_warning)));
+        out.push(format!("WidescreenStoryboard: {}", flag(self.widescreen_storyboard)));
+        out.push(format!("SamplesMatchPlaybackRate: {}", flag(self.samples_match_playback_rate)));
+        out.push(String::new());
+
+        out.push("[Editor]".to_string());
+        if !self.bookmarks.is_empty() {
+            let bookmarks:Vec<String> = self.bookmarks.iter().map(|b| b.to_string()).collect();
+            out.push(format!("Bookmarks: {}", bookmarks.join(",")));
+        }
+        out.push(format!("DistanceSpacing: {}", self.distance_spacing));
+        out.push(format!("BeatDivisor: {}", self.beat_divisor));
+        out.push(format!("GridSize: {}", self.grid_size));
+        out.push(format!("TimelineZoom: {}", self.timeline_zoom));
+        out.push(String::new());
+
+        out.push("[Metadata]".to_string());
+        out.push(format!("Title:{}", self.title));
+        out.push(format!("TitleUnicode:{}", self.title_unicode));
+        out.push(format!("Artist:{}", self.artist));
+        out.push(format!("ArtistUnicode:{}", self.artist_unicode));
+        out.push(format!("Creator:{}", self.creator));
+        out.push(format!("Version:{}", self.difficulty));
+        out.push(format!("Source:{}", self.source));
+        out.push(format!("Tags:{}", self.tags.join(" ")));
+        out.push(format!("BeatmapID:{}", self.id));
+        out.push(format!("BeatmapSetID:{}", self.beatmap_set_id));
+        out.push(String::new());
+
+        out.push("[Difficulty]".to_string());
+        out.push(format!("HPDrainRate:{}", self.hp_drain));
+        out.push(format!("CircleSize:{}", self.circle_size));
+        out.push(format!("OverallDifficulty:{}", self.overall_difficulty));
+        out.push(format!("ApproachRate:{}", self.approach_rate));
+        out.push(format!("SliderMultiplier:{}", self.slider_multiplier));
+        out.push(format!("SliderTickRate:{}", self.slider_tick_rate));
+        out.push(String::new());
+
+        out.push("[Events]".to_string());
+        out.push("//Background and Video events".to_string());
+        for event in &self.events {
+            match *event {
+                Event::Break { .. } => {}
+                _ => out.push(event.to_osu())
+            }
+        }
+        out.push("//Break Periods".to_string());
+        for event in &self.events {
+            if let Event::Break { .. } = *event {
+                out.push(event.to_osu());
+            }
+        }
+        out.extend(self.storyboard.to_osu());
+        out.push(String::new());
+
+        out.push("[TimingPoints]".to_string());
+        for timing in &self.timing_points {
+            out.push(timing.to_osu());
+        }
+        out.push(String::new());
+
+        out.push("[Colours]".to_string());
+        for (i, color) in self.colors.iter().enumerate() {
+            out.push(format!("Combo{} : {},{},{}", i + 1, color.0, color.1, color.2));
+        }
+        out.push(String::new());
+
+        out.push("[HitObjects]".to_string());
+        for obj in &self.objects {
+            out.push(obj.to_osu());
+        }
+
+        out.push(String::new());
+        out.join("\r\n")
+    }
+}
+
+/*
+  Comma separated fields of line. Only their bounds are stored, in a buffer
+  kept by LineReader, so splitting a line doesn't allocate.
+*/
+struct Fields<'a> {
+    line: &'a str,
+    bounds: &'a [(usize, usize)],
+}
+
+impl<'a> Fields<'a> {
+    fn split(line: &'a str, buffer: &'a mut Vec<(usize, usize)>) -> Fields<'a> {
+        buffer.clear();
+        let mut start = 0;
+        for (i, _) in line.match_indices(',') {
+            buffer.push((start, i));
+            start = i + 1;
+        }
+        buffer.push((start, line.len()));
+        Fields { line: line, bounds: buffer }
+    }
+
+    fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    //Field at index, which must exist
+    fn get(&self, index: usize) -> &'a str {
+        let (start, end) = self.bounds[index];
+        &self.line[start..end]
+    }
+
+    //Field at index or MissingField error for the whole line
+    fn field(&self, index: usize, name: &'static str) -> Result<&'a str, BeatmapParseError> {
+        if index < self.len() {
+            Ok(self.get(index))
+        } else {
+            Err(BeatmapParseError::new(ParseErrorKind::MissingField, name, self.line))
+        }
+    }
+}
+
+//First two parts of "a:b", MissingField error for the whole line if there is no b
+fn pair<'a>(text: &'a str, field: &'static str, line: &str) -> Result<(&'a str, &'a str), BeatmapParseError> {
+    let mut parts = text.split(':');
+    let first = parts.next().unwrap_or("");
+    match parts.next() {
+        Some(second) => Ok((first, second)),
+        None => Err(BeatmapParseError::new(ParseErrorKind::MissingField, field, line)),
+    }
+}
+
+/*
+  State of reading .osu line by line, shared by reading from lines and from stream.
+  In lenient mode bad lines are skipped and kept as warnings,
+  otherwise the first bad line stops parsing.
+  Missing format header is fatal in both modes.
+*/
+struct LineReader {
+    section: Option<SectionType>,
+    header_read: bool,
+    lenient: bool,
+    warnings: Vec<BeatmapParseError>,
+    //bounds of fields of current line, reused for every line
+    fields: Vec<(usize, usize)>,
+}
+
+impl LineReader {
+    fn new(lenient: bool) -> LineReader {
+        LineReader { section: None, header_read: false, lenient: lenient, warnings: Vec::new(), fields: Vec::new() }
+    }
+
+    //n is 1-based line number
+    fn read_line(&mut self, map: &mut Beatmap, n: usize, raw: &str) -> Result<(), BeatmapParseError> {
+        let line = raw.trim_left_matches('\u{feff}').trim();
+        if line.is_empty() || line.starts_with("//") {
+            return Ok(());
+        }
+        if !self.header_read {
+            if !line.starts_with(FORMAT_HEADER) {
+                return Err(BeatmapParseError::new(ParseErrorKind::MissingHeader, "header", line).at(None, n));
+            }
+            map.format_version = try!(
+                parse_field("version", &line[FORMAT_HEADER.len()..]).map_err(|e| e.at(None, n))
+            );
+            self.header_read = true;
+            return Ok(());
+        }
+        match line {
+            //setting up mode
+            "[General]" => { self.section = Some(SectionType::General) }
+            "[Editor]" => { self.section = Some(SectionType::Editor) }
+            "[Metadata]" => { self.section = Some(SectionType::Metadata) }
+            "[Difficulty]" => { self.section = Some(SectionType::Difficulty) }
+            "[Events]" => { self.section = Some(SectionType::Events) }
+            "[TimingPoints]" => { self.section = Some(SectionType::TimingPoints) }
+            "[Colours]" => { self.section = Some(SectionType::Colours) }
+            "[HitObjects]" => { self.section = Some(SectionType::HitObjects) }
+            _ if line.starts_with('[') && line.ends_with(']') => { self.section = None }
+            _ => {
+                if let Some(s) = self.section {
+                    //storyboard commands are nested with leading spaces
+                    let line = match s {
+                        SectionType::Events => raw.trim_right(),
+                        _ => line
+                    };
+                    if let Err(e) = map.parse_line(s, line, &mut self.fields) {
+                        let e = e.at(self.section, n);
+                        if self.lenient {
+                            self.warnings.push(e);
+                        } else {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Beatmap {
+    /*
+      Same as from_osu, but reads lines one by one into a single buffer
+      without keeping the whole file. Lines are split without allocating,
+      only values kept in the map are.
+    */
+    pub fn from_reader<R: BufRead>(&mut self, input: R) -> Result<(), BeatmapParseError> {
+        self.parse_stream(input, false, false).map(|_| ())
+    }
+
+    pub fn from_reader_lenient<R: BufRead>(&mut self, input: R) -> Result<Vec<BeatmapParseError>, BeatmapParseError> {
+        self.parse_stream(input, true, false)
+    }
+
+    /*
+        Fast path for scanning libraries: stops at [HitObjects],
+        so map has everything but objects and stats depending on them.
+        Sections written after [HitObjects] are not read.
+    */
+    pub fn metadata_from_reader<R: BufRead>(&mut self, input: R) -> Result<(), BeatmapParseError> {
+        self.parse_stream(input, false, true).map(|_| ())
+    }
+
+    fn parse_lines(&mut self, lines: &Vec<&str>, lenient: bool) -> Result<Vec<BeatmapParseError>, BeatmapParseError> {
+        let mut reader = LineReader::new(lenient);
+        //marks missing ApproachRate
+        self.approach_rate = -1.0;
+        for (n, line) in lines.iter().enumerate() {
+            try!(reader.read_line(self, n + 1, line));
+        }
+        self.finish_reading(reader)
+    }
+
+    fn parse_stream<R: BufRead>(&mut self, mut input: R, lenient: bool, metadata_only: bool) -> Result<Vec<BeatmapParseError>, BeatmapParseError> {
+        let mut reader = LineReader::new(lenient);
+        self.approach_rate = -1.0;
+        //one buffer is reused for every line
+        let mut line = String::new();
+        let mut n = 0;
+        loop {
+            line.clear();
+            n += 1;
+            match input.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Err(BeatmapParseError::new(ParseErrorKind::Io, "file", &e.to_string()).at(reader.section, n)),
+            }
+            try!(reader.read_line(self, n, &line));
+            if metadata_only && reader.section == Some(SectionType::HitObjects) {
+                break;
+            }
+        }
+        self.finish_reading(reader)
+    }
+
+    fn finish_reading(&mut self, reader: LineReader) -> Result<Vec<BeatmapParseError>, BeatmapParseError> {
+        if !reader.header_read {
+            return Err(BeatmapParseError::new(ParseErrorKind::MissingHeader, "header", ""));
+        }
+        //old maps have no ApproachRate, OD is used for both
+        if self.approach_rate < 0.0 {
+            self.approach_rate = self.overall_difficulty;
+        }
+        self.post_process();
+        Ok(reader.warnings)
+    }
+
+    fn index_timing_points(&mut self) {
+        self.uninherited_points = (0..self.timing_points.len()).filter(|i| !self.timing_points[*i].inherited).collect();
+        self.inherited_points = (0..self.timing_points.len()).filter(|i| self.timing_points[*i].inherited).collect();
+    }
+
+    //Sorted by start time
+    pub fn objects(&self) -> &Vec<HitObject> {
+        &self.objects
+    }
+
+    //Objects in time order
+    pub fn iter_objects(&self) -> slice::Iter<'_, HitObject> {
+        self.objects.iter()
+    }
+
+    //Objects starting in [start, end] range
+    pub fn objects_between(&self, start: i32, end: i32) -> &[HitObject] {
+        let from = count_before(self.objects.len(), start.saturating_sub(1), |i| self.objects[i].time_start);
+        let to = count_before(self.objects.len(), end, |i| self.objects[i].time_start);
+        if from < to { &self.objects[from..to] } else { &self.objects[0..0] }
+    }
+
+    //Latest object started at or before time, which may already be over
+    pub fn object_at(&self, time: i32) -> Option<&HitObject> {
+        match count_before(self.objects.len(), time, |i| self.objects[i].time_start) {
+            0 => None,
+            n => self.objects.get(n - 1),
+        }
+    }
+
+    pub fn timing_points(&self) -> &Vec<TimingPoint> {
+        &self.timing_points
+    }
+
+    //Count of indexed points starting at or before time
+    fn points_before(&self, indexes: &[usize], time: i32) -> usize {
+        count_before(indexes.len(), time, |i| self.timing_points[indexes[i]].time_start)
+    }
+
+    //Red line active at time. Objects before the first one use the first one
+    pub fn uninherited_point_at(&self, time: i32) -> Option<&TimingPoint> {
+        match self.points_before(&self.uninherited_points, time) {
+            0 => self.uninherited_points.first(),
+            n => self.uninherited_points.get(n - 1),
+        }.map(|i| &self.timing_points[*i])
+    }
+
+    //Green line active at time. Every red line resets it
+    pub fn inherited_point_at(&self, time: i32) -> Option<&TimingPoint> {
+        let n = self.points_before(&self.inherited_points, time);
+        if n == 0 {
+            return None;
+        }
+        let point = &self.timing_points[self.inherited_points[n - 1]];
+        match self.uninherited_point_at(time) {
+            Some(red) if red.time_start > point.time_start => None,
+            _ => Some(point)
+        }
+    }
+
+    //Latest point of any kind at time, it sets samples and kiai
+    pub fn timing_point_at(&self, time: i32) -> Option<&TimingPoint> {
+        match count_before(self.timing_points.len(), time, |i| self.timing_points[i].time_start) {
+            0 => self.timing_points.first(),
+            n => self.timing_points.get(n - 1),
+        }
+    }
+
+    //120 BPM for maps without timing at all
+    pub fn beat_length_at(&self, time: i32) -> f32 {
+        self.uninherited_point_at(time).map_or(500.0, |tp| tp.beat_length)
+    }
+
+    pub fn bpm_at(&self, time: i32) -> f32 {
+        60000.0 / self.beat_length_at(time)
+    }
+
+    //Slider velocity multiplier, 2.0 for -50 inherited point
+    pub fn slider_velocity_at(&self, time: i32) -> f32 {
+        self.inherited_point_at(time).map_or(1.0, |tp| 1.0 / tp.get_slider_multiplier())
+    }
+
+    pub fn kiai_at(&self, time: i32) -> bool {
+        self.timing_point_at(time).map(|tp| tp.kiai) == Some(true)
+    }
+
+    //Falls back to SampleSet from [General] if timing point doesn't set it
+    pub fn sample_set_at(&self, time: i32) -> SampleSet {
+        match self.timing_point_at(time).map(|tp| tp.sample_type) {
+            Some(SampleSet::NONE) | None => self.sample_set,
+            Some(set) => set
+        }
+    }
+
+    pub fn sample_index_at(&self, time: i32) -> i32 {
+        self.timing_point_at(time).map_or(0, |tp| tp.sample_type_custom)
+    }
+
+    pub fn sample_volume_at(&self, time: i32) -> i32 {
+        self.timing_point_at(time).map_or(100, |tp| tp.sample_volume)
+    }
+
+    //Values computed from the whole map once everything is read
+    fn post_process(&mut self) {
+        //stable sort keeps file order for objects at the same time
+        self.objects.sort_by_key(|o| o.time_start);
+        self.timing_points.sort_by_key(|tp| tp.time_start);
+        self.index_timing_points();
+        self.apply_combos();
+        self.apply_slider_durations();
+        if self.mode == GameMode::Osu {
+            let preempt = self.difficulty_attributes().preempt();
+            apply_stacking(&mut self.objects, preempt, self.stack_leniency, self.format_version);
+        }
+        self.stat = self.compute_stat();
+    }
+
+    pub fn stat(&self) -> &BeatmapStat {
+        &self.stat
+    }
+
+    fn compute_stat(&self) -> BeatmapStat {
+        let mut stat = BeatmapStat { ..Default::default() };
+        for obj in &self.objects {
+            match obj.obj_type {
+                HitObjectType::Circle => { stat.circles += 1 }
+                HitObjectType::Slider => { stat.sliders += 1 }
+                HitObjectType::Spinner => { stat.spinners += 1 }
+                HitObjectType::Hold => { stat.holds += 1 }
+            }
+            stat.max_combo += obj.max_combo();
+        }
+
+        let first = self.objects.first().map_or(0, |o| o.time_start);
+        let last = self.objects.iter().map(|o| o.time_end()).max().unwrap_or(0);
+        stat.total_length = last;
+        let breaks:i32 = self.breaks().iter().map(|b| (b.1.min(last) - b.0.max(first)).max(0)).sum();
+        stat.drain_time = (last - first - breaks).max(0);
+
+        /*
+          Every red line lasts until the next one, the last one until end of map.
+          First one counts from the song start, lines after the last object are
+          ignored, for BPM range too, as they only end the song.
+        */
+        let mut durations:Vec<(f32, i32)> = Vec::new();
+        let reds = &self.uninherited_points;
+        for (n, i) in reds.iter().enumerate() {
+            let tp = &self.timing_points[*i];
+            if tp.beat_length <= 0.0 || (n > 0 && tp.time_start > last) {
+                continue;
+            }
+            let start = if n == 0 { 0 } else { tp.time_start };
+            let end = match reds.get(n + 1) {
+                Some(next) => self.timing_points[*next].time_start.min(last),
+                None => last
+            };
+            let duration = (end - start).max(0);
+            //same BPM written with different precision counts together
+            let beat_length = (tp.beat_length * 1000.0).round() / 1000.0;
+            match durations.iter().position(|d| d.0 == beat_length) {
+                Some(d) => { durations[d].1 += duration }
+                None => { durations.push((beat_length, duration)) }
+            }
+        }
+        if durations.is_empty() {
+            durations.push((self.beat_length_at(0), 0));
+        }
+        let bpm = |beat_length: f32| 60000.0 / beat_length;
+        stat.min_bpm = bpm(durations.iter().fold(0.0f32, |m, d| m.max(d.0)));
+        stat.max_bpm = bpm(durations.iter().fold(durations[0].0, |m, d| m.min(d.0)));
+        //first of equally long ones
+        let mut dominant = durations[0];
+        for d in &durations {
+            if d.1 > dominant.1 {
+                dominant = *d;
+            }
+        }
+        stat.dominant_bpm = bpm(dominant.0);
+        stat
+    }
+
+    //Gameplay values of difficulty settings
+    pub fn difficulty_attributes(&self) -> DifficultyAttributes {
+        DifficultyAttributes::new(self.circle_size, self.approach_rate, self.overall_difficulty, self.hp_drain)
+    }
+
+    //Takes CS, AR, OD and HP, clock rate is ignored. Stacks are computed again
+    pub fn set_difficulty(&mut self, attrs: &DifficultyAttributes) {
+        self.circle_size = attrs.circle_size;
+        self.approach_rate = attrs.approach_rate;
+        self.overall_difficulty = attrs.overall_difficulty;
+        self.hp_drain = attrs.hp_drain;
+        self.post_process();
+    }
+
+    //Mirrors every object upside down, as Hard Rock does
+    pub fn flip_vertically(&mut self) {
+        for obj in self.objects.iter_mut() {
+            for p in obj.points.iter_mut() {
+                p.1 = MAX_Y - p.1;
+            }
+            obj.prepare_slider();
+        }
+        self.post_process();
+    }
+
+    /*
+        Slider goes 100 * SliderMultiplier osu!pixels per beat,
+        faster or slower if inherited point changes velocity.
+        Ticks are placed every beat / SliderTickRate of travel.
+    */
+    fn apply_slider_durations(&mut self) {
+        let px_per_beat = 100.0 * if self.slider_multiplier > 0.0 { self.slider_multiplier } else { 1.4 };
+        let tick_rate = if self.slider_tick_rate > 0.0 { self.slider_tick_rate } else { 1.0 };
+        let mut timings = Vec::with_capacity(self.objects.len());
+        for obj in &self.objects {
+            if obj.obj_type == HitObjectType::Slider {
+                let multiplier = self.inherited_point_at(obj.time_start).map_or(1.0, |tp| tp.get_slider_multiplier());
+                let beat_length = self.beat_length_at(obj.time_start) * multiplier;
+                //before v8 ticks don't follow slider velocity
+                let tick_distance = if self.format_version < 8 {
+                    px_per_beat / tick_rate
+                } else {
+                    px_per_beat / (tick_rate * multiplier)
+                };
+                timings.push((obj.slider_length() / px_per_beat * beat_length, px_per_beat / beat_length, tick_distance));
+            } else {
+                timings.push((0.0, 0.0, 0.0));
+            }
+        }
+        for (obj, (span, velocity, tick_distance)) in self.objects.iter_mut().zip(timings) {
+            if obj.obj_type == HitObjectType::Slider {
+                obj.span_duration = span;
+                obj.length = (span * obj.slider_repeats.max(1) as f32).round() as i32;
+                obj.path.set_timing(obj.time_start, span, obj.slider_repeats);
+                obj.generate_nested(velocity, tick_distance);
+            }
+        }
+    }
+
+    /*
+        Fills combo_index, combo_number and end_combo.
+        First object always starts new combo. Spinners don't start combo themselves,
+        but next object does if spinner had new combo flag (always in v8 and older).
+        Their colour skip goes to that object.
+    */
+    fn apply_combos(&mut self) {
+        let colour_count = if self.colors.is_empty() { DEFAULT_COMBO_COLORS.len() } else { self.colors.len() } as i32;
+        let mut force_new_combo = false;
+        let mut extra_skip = 0;
+        let mut combo_index = 0;
+        let mut combo_number = 0;
+        let mut first = true;
+        for obj in self.objects.iter_mut() {
+            if obj.obj_type == HitObjectType::Spinner {
+                force_new_combo |= self.format_version <= 8 || obj.new_combo;
+                extra_skip += obj.colour_skip;
+                combo_number += 1;
+            } else {
+                if first || obj.new_combo || force_new_combo {
+                    let skip = obj.colour_skip + extra_skip;
+                    combo_index = if first { skip } else { combo_index + 1 + skip } % colour_count;
+                    combo_number = 1;
+                    force_new_combo = false;
+                    extra_skip = 0;
+                    first = false;
+                } else {
+                    combo_number += 1;
+                }
+            }
+            obj.combo_index = combo_index;
+            obj.combo_number = combo_number.max(1);
+            obj.end_combo = false;
+        }
+
+        //object before the one which starts new combo ends it
+        let len = self.objects.len();
+        for i in 0..len {
+            let next_starts = i + 1 == len || self.objects[i + 1].combo_number == 1;
+            self.objects[i].end_combo = next_starts;
+        }
+    }
+
+    fn parse_line(&mut self, section: SectionType, line: &str, buffer: &mut Vec<(usize, usize)>) -> Result<(), BeatmapParseError> {
+        match section {
+            SectionType::General => {
+                let (key, value) = try!(key_value(line));
+                match key {
+                    "AudioFilename" => { self.audio_filename = value.to_string() }
+                    "AudioLeadIn" => { self.audio_lead_in = try!(parse_field("AudioLeadIn", value)) }
+                    "PreviewTime" => { self.preview_time = try!(parse_field("PreviewTime", value)) }
+                    "Countdown" => { self.countdown_type = try!(parse_field("Countdown", value)) }
+                    "SampleSet" => {
+                        self.sample_set = match value {
+                            "Normal" => { SampleSet::NORMAL }
+                            "Soft" => { SampleSet::SOFT }
+                            "Drum" => { SampleSet::DRUM }
+                            "None" => { SampleSet::NONE }
+                            _ => { SampleSet::NONE }
+                        }
+                    }
+                    "StackLeniency" => { self.stack_leniency = try!(parse_field("StackLeniency", value)) }
+                    "Mode" => {
+                        let index = try!(parse_field("Mode", value));
+                        self.mode = try!(GameMode::from_index(index)
+                            .ok_or(BeatmapParseError::new(ParseErrorKind::InvalidValue, "Mode", value)));
+                    }
+                    "LetterboxInBreaks" => { self.letterbox_in_breaks = try!(parse_flag("LetterboxInBreaks", value)) }
+                    "SkinPreference" => { self.skin_preference = value.to_string() }
+                    "SpecialStyle" => { self.special_style = try!(parse_flag("SpecialStyle", value)) }
+                    "UseSkinSprites" => { self.use_skin_sprites = try!(parse_flag("UseSkinSprites", value)) }
+                    "StoryFireInFront" => { self.story_fire_in_front = try!(parse_flag("StoryFireInFront", value)) }
+                    "EpilepsyWarning" => { self.epilepsy_warning = try!(parse_flag("EpilepsyWarning", value)) }
+                    "WidescreenStoryboard" => { self.widescreen_storyboard = try!(parse_flag("WidescreenStoryboard", value)) }
+                    "SamplesMatchPlaybackRate" => { self.samples_match_playback_rate = try!(parse_flag("SamplesMatchPlaybackRate", value)) }
+                    _ => {}
+                }
+            }
+            SectionType::Metadata => {
+                let (key, value) = try!(key_value(line));
+                match key {
+                    "Title" => { self.title = value.to_string() }
+                    "TitleUnicode" => { self.title_unicode = value.to_string() }
+                    "Artist" => { self.artist = value.to_string() }
+                    "ArtistUnicode" => { self.artist_unicode = value.to_string() }
+                    "Creator" => { self.creator = value.to_string() }
+                    "Version" => { self.difficulty = value.to_string() }
+                    "Source" => { self.source = value.to_string() }
+                    "Tags" => { self.tags = value.split_whitespace().map(|t| t.to_string()).collect() },
+                    "BeatmapID" => { self.id = try!(parse_field("BeatmapID", value)) }
+                    "BeatmapSetID" => { self.beatmap_set_id = try!(parse_field("BeatmapSetID", value)) }
+                    _ => {}
+                }
+            }
+            SectionType::Difficulty => {
+                let (key, value) = try!(key_value(line));
+                match key {
+                    "HPDrainRate" => { self.hp_drain = try!(parse_field("HPDrainRate", value)) }
+                    "CircleSize" => { self.circle_size = try!(parse_field("CircleSize", value)) }
+                    "OverallDifficulty" => { self.overall_difficulty = try!(parse_field("OverallDifficulty", value)) }
+                    "ApproachRate" => { self.approach_rate = try!(parse_field("ApproachRate", value)) }
+                    "SliderMultiplier" => { self.slider_multiplier = try!(parse_field("SliderMultiplier", value)) }
+                    "SliderTickRate" => { self.slider_tick_rate = try!(parse_field("SliderTickRate", value)) }
+                    _ => {}
+                }
+            }
+            SectionType::TimingPoints => {
+                let tokens = Fields::split(line, buffer);
+                let mut timing = TimingPoint { meter: 4, sample_volume: 100, ..Default::default() };
+                let time_start:f32 = try!(parse_field("time", try!(tokens.field(0, "time"))));
+                timing.time_start = (time_start.round()) as i32;
+                let vel:f32 = try!(parse_field("beatLength", try!(tokens.field(1, "beatLength"))));
+                if vel.is_sign_negative() {
+                    timing.inherited = true;
+                    timing.velocity = vel;
+                } else {
+                    timing.inherited = false;
+                    timing.beat_length = vel;
+                }
+                //fields below were added in later format versions
+                if tokens.len() > 2 {
+                    timing.meter = try!(parse_field("meter", tokens.get(2)));
+                }
+                if tokens.len() > 3 {
+                    timing.sample_type = SampleSet::from_index(try!(parse_field("sampleSet", tokens.get(3))));
+                }
+                if tokens.len() > 4 {
+                    timing.sample_type_custom = try!(parse_field("sampleIndex", tokens.get(4)));
+                }
+                if tokens.len() > 5 {
+                    timing.sample_volume = try!(parse_field("volume", tokens.get(5)));
+                }
+                if tokens.len() > 7 {
+                    let effects:i32 = try!(parse_field("effects", tokens.get(7)));
+                    timing.kiai = effects & 1 > 0;
+                }
+                self.timing_points.push(timing);
+            }
+            SectionType::HitObjects => {
+                let tokens = Fields::split(line, buffer);
+                let type_field = try!(tokens.field(3, "type"));
+                let flags = HitObjectFlags::from_bits(try!(parse_field("type", type_field)));
+                let obj_type = try!(flags.object_type()
+                    .ok_or(BeatmapParseError::new(ParseErrorKind::InvalidValue, "type", type_field)));
+                let time_start:i32 = try!(parse_field("time", try!(tokens.field(2, "time"))));
+                let hitsound:i32 = try!(parse_field("hitSound", try!(tokens.field(4, "hitSound"))));
+                let mut obj = HitObject::new(HitObjectType::Circle);
+                obj.with_params(time_start, false, HitSoundType::from_bits(hitsound));
+                obj.new_combo = flags.new_combo();
+                obj.colour_skip = flags.colour_skip();
+                obj.add_point( (
+                    try!(parse_field("x", tokens.get(0))),
+                    try!(parse_field("y", tokens.get(1)))
+                ) );
+                match obj_type {
+                    HitObjectType::Spinner => {
+                        obj.obj_type = HitObjectType::Spinner;
+                        let time_end: i32 = try!(parse_field("endTime", try!(tokens.field(5, "endTime"))));
+                        obj.with_spinner_length( time_end - obj.time_start );
+                        if tokens.len() > 6 {
+                            obj.hit_sample = try!(HitSample::from_osu(tokens.get(6)));
+                        }
+                    }
+                    HitObjectType::Slider => {
+                        obj.obj_type = HitObjectType::Slider;
+                        obj.slider_repeats = try!(parse_field("slides", try!(tokens.field(6, "slides"))));
+                        if tokens.len() > 7 {
+                            obj.pixel_length = try!(parse_field("length", tokens.get(7)));
+                        }
+                        let mut slider_parts = try!(tokens.field(5, "curvePoints")).split('|');
+                        match slider_parts.next().unwrap_or("") {
+                            "B" => {
+                                obj.slider_type = SliderType::SliderBezier;
+                            }
+                            "C" => {
+                                //deprecated, but old ranked maps still have it
+                                obj.slider_type = SliderType::SliderCatmul;
+                            }
+                            "P" => {
+                                obj.slider_type = SliderType::SliderPassthrough;
+                            }
+                            "L" => {
+                                obj.slider_type = SliderType::SliderLinear;
+                            }
+                            _ => {}
+                        }
+                        for part in slider_parts {
+                            let (x, y) = try!(pair(part, "curvePoints", line));
+                            obj.add_point( (
+                                try!(parse_field("curvePoints", x)),
+                                try!(parse_field("curvePoints", y))
+                            ) );
+                        }
+                        obj.prepare_slider();
+
+                        //every edge (head, repeats, tail) has own hitsound and sample sets
+                        let edges = obj.slider_repeats.max(1) as usize + 1;
+                        if tokens.len() > 8 && !tokens.get(8).is_empty() {
+                            for sound in tokens.get(8).split('|') {
+                                obj.edge_sounds.push(HitSoundType::from_bits(try!(parse_field("edgeSounds", sound))));
+                            }
+                        }
+                        if tokens.len() > 9 && !tokens.get(9).is_empty() {
+                            for sets in tokens.get(9).split('|') {
+                                let (normal, addition) = try!(pair(sets, "edgeSets", line));
+                                obj.edge_sets.push( (
+                                    SampleSet::from_index(try!(parse_field("edgeSets", normal))),
+                                    SampleSet::from_index(try!(parse_field("edgeSets", addition)))
+                                ) );
+                            }
+                        }
+                        while obj.edge_sounds.len() < edges {
+                            let sound = obj.hitsound;
+                            obj.edge_sounds.push(sound);
+                        }
+                        while obj.edge_sets.len() < edges {
+                            obj.edge_sets.push( (SampleSet::NONE, SampleSet::NONE) );
+                        }
+                        if tokens.len() > 10 {
+                            obj.hit_sample = try!(HitSample::from_osu(tokens.get(10)));
+                        }
+                    }
+                    HitObjectType::Hold => {
+                        //endTime:hitSample
+                        obj.obj_type = HitObjectType::Hold;
+                        let mut parts = try!(tokens.field(5, "endTime")).splitn(2, ':');
+                        let time_end: i32 = try!(parse_field("endTime", parts.next().unwrap_or("")));
+                        obj.length = time_end - obj.time_start;
+                        if let Some(sample) = parts.next() {
+                            obj.hit_sample = try!(HitSample::from_osu(sample));
+                        }
+                    }
+                    HitObjectType::Circle => {
+                        if tokens.len() > 5 {
+                            obj.hit_sample = try!(HitSample::from_osu(tokens.get(5)));
+                        }
+                    }
+                }
+
+                self.objects.push(obj);
+            }
+            SectionType::Events => {
+                let nested = line.starts_with(' ') || line.starts_with('_');
+                if !nested {
+                    if let Some(event) = try!(Event::from_osu(line)) {
+                        self.events.push(event);
+                        return Ok(());
+                    }
+                }
+                try!(self.storyboard.parse_line(line));
+            }
+            SectionType::Colours => {
+                let (key, value) = try!(key_value(line));
+                if key.starts_with("Combo") {
+                    let color_frags:Vec<&str> = value.split(',').collect();
+                    let mut color = (0,0,0,1.0);
+                    color.0 = try!(parse_field("red", color_frags[0]));
+                    color.1 = try!(parse_field("green", try!(token(&color_frags, 1, "green", line))));
+                    color.2 = try!(parse_field("blue", try!(token(&color_frags, 2, "blue", line))));
+                    self.colors.push(color);
+                }
+            }
+            SectionType::Editor => {
+                let (key, value) = try!(key_value(line));
+                match key {
+                    "Bookmarks" => {
+                        self.bookmarks.clear();
+                        for b in value.split(',').filter(|b| !b.trim().is_empty()) {
+                            self.bookmarks.push(try!(parse_field("Bookmarks", b)));
+                        }
+                        self.bookmarks.sort();
+                    }
+                    "DistanceSpacing" => { self.distance_spacing = try!(parse_field("DistanceSpacing", value)) }
+                    "BeatDivisor" => { self.beat_divisor = try!(parse_field("BeatDivisor", value)) }
+                    "GridSize" => { self.grid_size = try!(parse_field("GridSize", value)) }
+                    "TimelineZoom" => { self.timeline_zoom = try!(parse_field("TimelineZoom", value)) }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//Binary search in sorted by time list: how many items start at or before time
+fn count_before<F: Fn(usize) -> i32>(len: usize, time: i32, time_of: F) -> usize {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if time_of(mid) <= time {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+//Splits "Key: Value" line of key-value sections. Value may contain ':' too
+fn key_value(line: &str) -> Result<(&str, &str), BeatmapParseError> {
+    match line.find(':') {
+        Some(i) => Ok( (line[..i].trim(), line[i + 1..].trim()) ),
+        None => Err(BeatmapParseError::new(ParseErrorKind::MissingField, "value", line))
+    }
+}
+
+//Boolean fields are stored as 0 and 1
+fn parse_flag(field: &'static str, value: &str) -> Result<bool, BeatmapParseError> {
+    let val:i32 = try!(parse_field(field, value));
+    Ok(val == 1)
+}
+
+fn flag(value: bool) -> i32 {
+    if value { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::{Read, BufReader};
+
+    #[test]
+    fn read_map() {
+        let mut btmp = super::Beatmap::new();
+        let mut f = File::open("tests/test.osu").unwrap();
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+        let mut lines = s.lines().collect();
+        btmp.from_osu(&mut lines).unwrap();
+
+        assert_eq!(btmp.format_version, 12);
+        assert!(!btmp.objects.is_empty());
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let mut f = File::open("tests/test.osu").unwrap();
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+
+        let mut original = super::Beatmap::new();
+        original.from_osu(&mut s.lines().collect()).unwrap();
+
+        let written = original.to_osu();
+        let mut reread = super::Beatmap::new();
+        reread.from_osu(&mut written.lines().collect()).unwrap();
+
+        assert_eq!(reread.format_version, FORMAT_VERSION);
+        original.format_version = FORMAT_VERSION;
+        assert_eq!(original, reread);
+        assert_eq!(written, reread.to_osu());
+    }
+
+    #[test]
+    fn breaks_and_letterbox() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[General]",
+            "LetterboxInBreaks: 1",
+            "[Events]",
+            "0,0,\"bg.png\",0,0",
+            "2,10000,15000",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        assert!(btmp.background().is_some());
+        assert!(btmp.video().is_none());
+        assert_eq!(btmp.breaks(), vec![(10000, 15000)]);
+        assert!(btmp.letterbox_at(12000));
+        assert!(!btmp.letterbox_at(9000));
+    }
+
+    #[test]
+    fn inline_storyboard() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Events]",
+            "0,0,\"bg.png\",0,0",
+            "//Storyboard Layer 0 (Background)",
+            "Sprite,Background,Centre,\"sb/bg.png\",320,240",
+            " F,0,0,1000,0,1",
+            " L,0,2",
+            "  R,0,0,500,0,3.14",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        assert_eq!(btmp.events().len(), 1);
+        assert_eq!(btmp.storyboard().sprites.len(), 1);
+        assert_eq!(btmp.storyboard().sprites[0].loops[0].commands.len(), 1);
+
+        let mut reread = super::Beatmap::new();
+        reread.from_osu(&mut btmp.to_osu().lines().collect()).unwrap();
+        assert_eq!(reread.storyboard(), btmp.storyboard());
+
+        //.osb sprites are not written into .osu
+        btmp.load_osb(&mut vec!["[Events]", "Sprite,Foreground,Centre,\"sb/fg.png\",320,240"]).unwrap();
+        assert_eq!(btmp.osb_storyboard().sprites.len(), 1);
+        assert_eq!(btmp.storyboard().sprites.len(), 1);
+        let mut reread = super::Beatmap::new();
+        reread.from_osu(&mut btmp.to_osu().lines().collect()).unwrap();
+        assert_eq!(reread.storyboard(), btmp.storyboard());
+        assert!(reread.osb_storyboard().sprites.is_empty());
+    }
+
+    #[test]
+    fn editor_section() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Editor]",
+            "Bookmarks: 5000,1000,3000,3000",
+            "DistanceSpacing: 1.3",
+            "BeatDivisor: 6",
+            "GridSize: 16",
+            "TimelineZoom: 2.5",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        assert_eq!(btmp.bookmarks(), &vec![1000, 3000, 3000, 5000]);
+        assert_eq!(btmp.bookmarks_between(1000, 3000), &[1000, 3000, 3000]);
+        assert_eq!(btmp.bookmarks_between(1001, 4999), &[3000, 3000]);
+        assert!(btmp.bookmarks_between(6000, 7000).is_empty());
+        assert_eq!(btmp.distance_spacing(), 1.3);
+        assert_eq!(btmp.beat_divisor(), 6);
+        assert_eq!(btmp.grid_size(), 16);
+        assert_eq!(btmp.timeline_zoom(), 2.5);
+
+        let mut reread = super::Beatmap::new();
+        reread.from_osu(&mut btmp.to_osu().lines().collect()).unwrap();
+        assert_eq!(reread.bookmarks(), btmp.bookmarks());
+        assert_eq!(reread.timeline_zoom(), 2.5);
+    }
+
+    #[test]
+    fn general_and_metadata() {
+        let mut btmp = super::Beatmap::new();
+        let mut f = File::open("tests/test.osu").unwrap();
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+        btmp.from_osu(&mut s.lines().collect()).unwrap();
+
+        assert_eq!(btmp.mode(), GameMode::Osu);
+        assert!(btmp.widescreen_storyboard());
+        assert!(btmp.story_fire_in_front());
+        assert_eq!(btmp.title_unicode(), "NO SCARED");
+        assert_eq!(btmp.artist_unicode(), "ONE OK ROCK");
+        assert_eq!(btmp.source(), "Black Rock Shooter: THE GAME");
+        assert_eq!(btmp.tags().len(), 6);
+        assert_eq!(btmp.tags()[0], "mad");
+        assert_eq!(btmp.id(), 306277);
+        assert_eq!(btmp.beatmap_set_id(), 119277);
+
+        let mut lines = vec![
+            "osu file format v14",
+            "[General]",
+            "Mode: 3",
+            "SkinPreference:Default: classic",
+            "SpecialStyle: 1",
+            "UseSkinSprites: 1",
+            "StoryFireInFront: 0",
+            "EpilepsyWarning: 1",
+            "SamplesMatchPlaybackRate: 1",
+        ];
+        let mut btmp = super::Beatmap::new();
+        btmp.from_osu(&mut lines).unwrap();
+        assert_eq!(btmp.mode(), GameMode::Mania);
+        assert_eq!(btmp.skin_preference(), "Default: classic");
+        assert!(btmp.special_style() && btmp.use_skin_sprites() && btmp.epilepsy_warning());
+        assert!(btmp.samples_match_playback_rate());
+        assert!(!btmp.story_fire_in_front());
+    }
+
+    #[test]
+    fn combos() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Colours]",
+            "Combo1 : 255,0,0",
+            "Combo2 : 0,255,0",
+            "Combo3 : 0,0,255",
+            "[HitObjects]",
+            "100,100,1000,1,0,0:0:0:0:",
+            "100,100,1100,1,0,0:0:0:0:",
+            "100,100,1200,5,0,0:0:0:0:",
+            "100,100,1300,21,0,0:0:0:0:",
+            "256,192,1400,12,0,2000,0:0:0:0:",
+            "100,100,2500,1,0,0:0:0:0:",
+            "100,100,2600,6,0,L|200:100,1,100",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        let numbers:Vec<i32> = btmp.objects.iter().map(|o| o.combo_number).collect();
+        let indexes:Vec<i32> = btmp.objects.iter().map(|o| o.combo_index).collect();
+        let ends:Vec<bool> = btmp.objects.iter().map(|o| o.end_combo).collect();
+        assert_eq!(numbers, vec![1, 2, 1, 1, 2, 1, 1]);
+        assert_eq!(indexes, vec![0, 0, 1, 0, 0, 1, 2]);
+        assert_eq!(ends, vec![false, true, true, false, true, true, true]);
+        assert_eq!(btmp.objects[3].colour_skip, 1);
+        assert_eq!(btmp.objects[6].obj_type, HitObjectType::Slider);
+        assert_eq!(btmp.combo_colour(&btmp.objects[6]), (0, 0, 255, 1.0));
+        assert_eq!(btmp.objects[3].to_osu(), "100,100,1300,21,0,0:0:0:0:");
+    }
+
+    #[test]
+    fn hitsounds_and_samples() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[HitObjects]",
+            "100,100,1000,1,14,1:2:3:70:hit.wav",
+            "100,100,2000,2,2,L|200:100,2,100,2|0|8,1:0|2:2|0:3,2:0:0:0:",
+            "100,100,3000,2,4,L|200:100,1,100",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+
+        let circle = &btmp.objects[0];
+        assert!(circle.hitsound.whistle() && circle.hitsound.finish() && circle.hitsound.clap());
+        assert_eq!(circle.hit_sample, HitSample {
+            normal_set: SampleSet::NORMAL,
+            addition_set: SampleSet::SOFT,
+            index: 3,
+            volume: 70,
+            filename: "hit.wav".to_string(),
+        });
+
+        let slider = &btmp.objects[1];
+        assert!(slider.hitsound.whistle() && !slider.hitsound.finish());
+        assert!(slider.edge_hitsound(0).whistle());
+        assert!(!slider.edge_hitsound(1).whistle());
+        assert!(slider.edge_hitsound(2).clap());
+        assert_eq!(slider.edge_sample_sets(1), (SampleSet::SOFT, SampleSet::SOFT));
+        assert_eq!(slider.edge_sample_sets(2), (SampleSet::NONE, SampleSet::DRUM));
+        assert_eq!(slider.hit_sample.normal_set, SampleSet::SOFT);
+
+        //no edge fields, every edge uses hitsound of slider
+        let plain = &btmp.objects[2];
+        assert_eq!(plain.edge_sounds.len(), 2);
+        assert!(plain.edge_hitsound(1).finish());
+
+        assert_eq!(slider.to_osu(), "100,100,2000,2,2,L|200:100,2,100,2|0|8,1:0|2:2|0:3,2:0:0:0:");
+    }
+
+    #[test]
+    fn mania_hold() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[HitObjects]",
+            "64,192,1000,128,2,1500:1:2:0:50:hold.wav",
+            "64,192,2000,1,0,0:0:0:0:",
+            "192,192,3000,132,0,3250",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        let hold = &btmp.objects[0];
+        assert_eq!(hold.obj_type, HitObjectType::Hold);
+        assert_eq!(hold.time_end(), 1500);
+        assert!(hold.hitsound.whistle());
+        assert_eq!(hold.hit_sample.volume, 50);
+        assert_eq!(hold.hit_sample.filename, "hold.wav");
+        assert_eq!(hold.to_osu(), "64,192,1000,128,2,1500:1:2:0:50:hold.wav");
+        assert_eq!(btmp.objects[1].obj_type, HitObjectType::Circle);
+        assert_eq!(btmp.objects[2].time_end(), 3250);
+        assert!(btmp.objects[2].new_combo);
+        assert_eq!(btmp.stat().holds(), 2);
+        assert_eq!(btmp.stat().objects(), 3);
+    }
+
+    #[test]
+    fn slider_length_and_duration() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Difficulty]",
+            "SliderMultiplier:1.4",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "2000,-50,4,1,0,100,0,0",
+            "[HitObjects]",
+            "100,100,1000,2,0,L|200:100,1,70",
+            "100,100,2500,2,0,L|200:100,2,140",
+            "100,100,4000,2,0,L|200:100,1,150",
+            "256,192,5000,12,0,6000",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+
+        let short = &btmp.objects[0];
+        assert_eq!(*short.path.points().last().unwrap(), (170.0, 100.0));
+        assert_eq!(short.span_duration, 250.0);
+        assert_eq!(short.time_end(), 1250);
+
+        //x2 velocity and one repeat
+        let fast = &btmp.objects[1];
+        assert_eq!(fast.span_duration, 250.0);
+        assert_eq!(fast.time_end(), 3000);
+
+        //path is extended along the last segment
+        let long = &btmp.objects[2];
+        assert_eq!(*long.path.points().last().unwrap(), (250.0, 100.0));
+        //ball goes back on repeat
+        assert_eq!(fast.path.position_at_time(2625.0), (170.0, 100.0));
+        assert_eq!(fast.path.position_at_time(3000.0), (100.0, 100.0));
+        assert_eq!(btmp.objects[3].time_end(), 6000);
+    }
+
+    #[test]
+    fn approach_rate_defaults_to_od() {
+        let mut old = super::Beatmap::new();
+        let mut lines = vec!["osu file format v7", "[Difficulty]", "CircleSize:4", "OverallDifficulty:8"];
+        old.from_osu(&mut lines).unwrap();
+        let attrs = old.difficulty_attributes();
+        assert_eq!(attrs.approach_rate, 8.0);
+        assert_eq!(attrs.preempt(), 750.0);
+        assert!((attrs.circle_radius() - 36.48).abs() < 0.001);
+
+        let mut new = super::Beatmap::new();
+        let mut lines = vec!["osu file format v14", "[Difficulty]", "OverallDifficulty:8", "ApproachRate:0"];
+        new.from_osu(&mut lines).unwrap();
+        assert_eq!(new.difficulty_attributes().preempt(), 1800.0);
+    }
+
+    #[test]
+    fn slider_nested_objects() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Difficulty]",
+            "SliderMultiplier:1.4",
+            "SliderTickRate:1",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "5000,-50,4,1,0,100,0,0",
+            "[HitObjects]",
+            "0,0,1000,2,0,L|350:0,2,350",
+            "0,0,4000,2,0,L|280:0,1,280",
+            "0,0,5000,2,0,L|560:0,1,560",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+
+        let nested = &btmp.objects[0].nested;
+        let kinds:Vec<NestedKind> = nested.iter().map(|n| n.kind).collect();
+        assert_eq!(kinds, vec![NestedKind::Head, NestedKind::Tick, NestedKind::Tick, NestedKind::Repeat,
+            NestedKind::Tick, NestedKind::Tick, NestedKind::LastTick, NestedKind::Tail]);
+        let times:Vec<f32> = nested.iter().map(|n| n.time).collect();
+        assert_eq!(times, vec![1000.0, 1500.0, 2000.0, 2250.0, 2500.0, 3000.0, 3464.0, 3500.0]);
+        assert_eq!(nested[3].position, (350.0, 0.0));
+        //second span goes back
+        assert_eq!(nested[4].position, (280.0, 0.0));
+        assert_eq!(nested[5].position, (140.0, 0.0));
+        assert_eq!(nested[7].position, (0.0, 0.0));
+
+        //x2 velocity makes ticks twice as far
+        let fast:Vec<f32> = btmp.objects[2].nested.iter().filter(|n| n.kind == NestedKind::Tick)
+            .map(|n| n.position.0).collect();
+        assert_eq!(fast, vec![280.0]);
+
+        //no tick right at the end
+        let ticks = btmp.objects[1].nested.iter().filter(|n| n.kind == NestedKind::Tick).count();
+        assert_eq!(ticks, 1);
+    }
+
+    #[test]
+    fn beatmap_stats() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Events]",
+            "2,5000,25000",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "10000,333.333,4,1,0,100,1,0",
+            "15000,-50,4,1,0,100,0,0",
+            "20000,500.0001,4,1,0,100,1,0",
+            "90000,250,4,1,0,100,1,0",
+            "[HitObjects]",
+            "0,0,2000,2,0,L|350:0,2,350",
+            "0,0,1000,1,0",
+            "256,192,31000,12,0,35000",
+            "100,100,30000,5,0",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+
+        let stat = btmp.stat();
+        assert_eq!((stat.circles(), stat.sliders(), stat.spinners()), (2, 1, 1));
+        assert_eq!(stat.objects(), 4);
+        assert_eq!(stat.max_combo(), 10);
+        assert_eq!(stat.total_length(), 35000);
+        //break is not drained
+        assert_eq!(stat.drain_time(), 14000);
+        //line after the last object is ignored
+        assert!((stat.min_bpm() - 120.0).abs() < 0.001);
+        assert!((stat.max_bpm() - 180.0).abs() < 0.01);
+        assert!((stat.dominant_bpm() - 120.0).abs() < 0.001);
+
+        let mut lines = vec![
+            "osu file format v14",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "1000,250,4,1,0,100,1,0",
+            "[HitObjects]",
+            "0,0,0,1,0",
+            "0,0,5000,1,0",
+        ];
+        let mut btmp = super::Beatmap::new();
+        btmp.from_osu(&mut lines).unwrap();
+        assert_eq!(btmp.stat().dominant_bpm(), 240.0);
+        assert_eq!(btmp.stat().min_bpm(), 120.0);
+
+        //trailing red line ending the song
+        let mut lines = vec![
+            "osu file format v14",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "6000,100,4,1,0,100,1,0",
+            "[HitObjects]",
+            "0,0,0,1,0",
+            "0,0,5000,1,0",
+        ];
+        let mut btmp = super::Beatmap::new();
+        btmp.from_osu(&mut lines).unwrap();
+        assert_eq!(btmp.stat().min_bpm(), 120.0);
+        assert_eq!(btmp.stat().max_bpm(), 120.0);
+    }
+
+    #[test]
+    fn builder_matches_parsed() {
+        let mut parsed = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Metadata]",
+            "Title:Test",
+            "[Difficulty]",
+            "CircleSize:4",
+            "ApproachRate:9",
+            "OverallDifficulty:8",
+            "HPDrainRate:6",
+            "SliderMultiplier:1.4",
+            "[Events]",
+            "2,3000,5000",
+            "[TimingPoints]",
+            "0,500,4,0,0,100,1,0",
+            "1000,-50,4,0,0,100,0,0",
+            "[HitObjects]",
+            "100,100,0,1,0",
+            "0,0,1000,2,0,L|280:0,1,280",
+            "256,192,6000,8,0,7000",
+        ];
+        parsed.from_osu(&mut lines).unwrap();
+
+        let built = BeatmapBuilder::new()
+            .title("Test")
+            .difficulty(&DifficultyAttributes::new(4.0, 9.0, 8.0, 6.0))
+            .timing_point(TimingPoint::new_inherited(1000, 2.0))
+            .timing_point(TimingPoint::new_uninherited(0, 500.0, 4))
+            .object(HitObject::spinner(6000, 7000))
+            .object(HitObject::slider(SliderType::SliderLinear, vec![(0, 0), (280, 0)], 1000, 1, 280.0))
+            .object(HitObject::circle((100, 100), 0))
+            .break_period(3000, 5000)
+            .build();
+        assert_eq!(built.timing_points(), parsed.timing_points());
+        assert_eq!(built.objects(), parsed.objects());
+        assert_eq!(built.stat(), parsed.stat());
+        assert_eq!(built.title(), "Test");
+        assert_eq!(built.approach_rate(), 9.0);
+        assert_eq!(built.format_version(), FORMAT_VERSION);
+        assert_eq!(built.objects()[1].length, 500);
+
+        let times:Vec<i32> = built.iter_objects().map(|o| o.time_start).collect();
+        assert_eq!(times, vec![0, 1000, 6000]);
+        assert_eq!(built.objects_between(0, 1000).len(), 2);
+        assert_eq!(built.objects_between(1, 6000)[0].time_start, 1000);
+        assert!(built.objects_between(2000, 5000).is_empty());
+        assert_eq!(built.object_at(5999).unwrap().time_start, 1000);
+        assert!(built.object_at(-1).is_none());
+        assert_eq!(built.colour_at_index(5), DEFAULT_COMBO_COLORS[1]);
+        assert_eq!(built.colour_at_index(-1), DEFAULT_COMBO_COLORS[0]);
+    }
+
+    #[test]
+    fn timing_lookups() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[General]",
+            "SampleSet: Soft",
+            "[TimingPoints]",
+            "1000,500,4,1,0,100,1,0",
+            "2000,-50,4,0,0,60,0,1",
+            "3000,-200,4,3,1,40,0,0",
+            "4000,300,4,2,0,80,1,0",
+            "4000,-80,4,2,0,80,0,0",
+            "6000,250,3,2,0,70,1,1",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+
+        assert_eq!(btmp.uninherited_point_at(0).unwrap().time_start(), 1000);
+        assert_eq!(btmp.uninherited_point_at(4500).unwrap().time_start(), 4000);
+        assert_eq!(btmp.bpm_at(1500), 120.0);
+        assert_eq!(btmp.bpm_at(6000), 240.0);
+
+        assert!(btmp.inherited_point_at(1500).is_none());
+        assert_eq!(btmp.slider_velocity_at(2500), 2.0);
+        assert_eq!(btmp.slider_velocity_at(3500), 0.5);
+        assert_eq!(btmp.slider_velocity_at(4000), 1.25);
+        //red line resets velocity
+        assert_eq!(btmp.slider_velocity_at(7000), 1.0);
+
+        assert!(!btmp.kiai_at(1999));
+        assert!(btmp.kiai_at(2000));
+        assert!(!btmp.kiai_at(3000));
+        assert!(btmp.kiai_at(6500));
+
+        assert_eq!(btmp.sample_set_at(2500), SampleSet::SOFT);
+        assert_eq!(btmp.sample_set_at(3500), SampleSet::DRUM);
+        assert_eq!(btmp.sample_index_at(3500), 1);
+        assert_eq!(btmp.sample_volume_at(2500), 60);
+        assert_eq!(btmp.sample_volume_at(500), 100);
+    }
+
+    #[test]
+    fn slider_curve_types() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v5",
+            "[HitObjects]",
+            "0,0,1000,2,0,C|100:100|200:0,1,0",
+            "0,0,2000,2,0,B|100:100|200:0,1,0",
+            "0,0,3000,2,0,P|100:100|200:0,1,0",
+            "0,0,4000,2,0,L|100:100,1,0",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        let types:Vec<SliderType> = btmp.objects.iter().map(|o| o.slider_type).collect();
+        assert_eq!(types, vec![SliderType::SliderCatmul, SliderType::SliderBezier,
+            SliderType::SliderPassthrough, SliderType::SliderLinear]);
+        //no length given, control points set it
+        assert_eq!(btmp.objects[3].slider_length(), btmp.objects[3].path.length());
+        assert!(btmp.objects[3].length > 0);
+        //catmull goes through control point, bezier doesn't
+        assert!(btmp.objects[0].path.points().contains(&(100.0, 100.0)));
+        assert!(!btmp.objects[1].path.points().contains(&(100.0, 100.0)));
+        assert!(btmp.objects[0].to_osu().contains("C|100:100|200:0"));
+    }
+
+    #[test]
+    fn slider_without_length() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Difficulty]",
+            "SliderMultiplier:1.4",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "[HitObjects]",
+            "0,0,1000,2,0,L|210:0,1",
+            "0,0,2000,2,0,L|210:0,2,0",
+            "0,0,4000,2,0,L|210:0,1,70",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+        //210 px at 140 px per beat is 1.5 beats
+        let missing = &btmp.objects[0];
+        assert_eq!(missing.path.length(), 210.0);
+        assert_eq!(missing.span_duration, 750.0);
+        assert_eq!(missing.time_end(), 1750);
+        let ticks = missing.nested.iter().filter(|n| n.kind == NestedKind::Tick).count();
+        assert_eq!(ticks, 1);
+
+        let zero = &btmp.objects[1];
+        assert_eq!(zero.path.length(), 210.0);
+        assert_eq!(zero.time_end(), 3500);
+
+        //given length still cuts the path
+        assert_eq!(btmp.objects[2].path.length(), 70.0);
+        assert_eq!(btmp.objects[2].time_end(), 4250);
+    }
+
+    #[test]
+    fn strict_parse_reports_bad_line() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "",
+            "[TimingPoints]",
+            "0,500,4,2,0,100,1,0",
+            "1000,-50,four,2,0,100,0,0",
+        ];
+        let err = btmp.from_osu(&mut lines).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidValue);
+        assert_eq!(err.section, Some(SectionType::TimingPoints));
+        assert_eq!(err.line, 5);
+        assert_eq!(err.field, "meter");
+        assert_eq!(err.text, "four");
+    }
+
+    #[test]
+    fn lenient_parse_skips_bad_lines() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[HitObjects]",
+            "256,192,1000,1,0,0:0:0:0:",
+            "256,192,oops,1,0,0:0:0:0:",
+            "256,192",
+            "100,100,2000,1,0,0:0:0:0:",
+        ];
+        let warnings = btmp.from_osu_lenient(&mut lines).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].kind, ParseErrorKind::InvalidValue);
+        assert_eq!(warnings[0].line, 4);
+        assert_eq!(warnings[1].kind, ParseErrorKind::MissingField);
+        assert_eq!(btmp.objects.len(), 2);
+    }
+
+    #[test]
+    fn missing_header_is_fatal() {
+        let mut btmp = super::Beatmap::new();
+        let mut lines = vec!["[General]", "AudioFilename: a.mp3"];
+        let err = btmp.from_osu_lenient(&mut lines).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingHeader);
+    }
+
+    #[test]
+    fn stream_matches_lines() {
+        let mut s = String::new();
+        File::open("tests/test.osu").unwrap().read_to_string(&mut s).unwrap();
+        let mut lines = s.lines().collect();
+        let mut from_lines = super::Beatmap::new();
+        from_lines.from_osu(&mut lines).unwrap();
+
+        let mut streamed = super::Beatmap::new();
+        streamed.from_reader(BufReader::new(File::open("tests/test.osu").unwrap())).unwrap();
+        assert_eq!(streamed, from_lines);
+
+        let mut metadata = super::Beatmap::new();
+        metadata.metadata_from_reader(s.as_bytes()).unwrap();
+        assert_eq!(metadata.title(), from_lines.title());
+        assert_eq!(metadata.version(), from_lines.version());
+        assert_eq!(metadata.approach_rate(), from_lines.approach_rate());
+        assert_eq!(metadata.timing_points(), from_lines.timing_points());
+        assert_eq!(metadata.stat().max_bpm(), from_lines.stat().max_bpm());
+        assert!(metadata.objects().is_empty());
+    }
+
+    #[test]
+    fn stream_errors() {
+        let mut btmp = super::Beatmap::new();
+        let err = btmp.from_reader(&b"osu file format v14\r\n[Metadata]\r\nTitle:\xff\r\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::Io);
+        assert_eq!(err.line, 3);
+
+        let err = btmp.from_reader(&b"osu file format v14\n[Difficulty]\nCircleSize:big\n"[..]).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidValue);
+        assert_eq!(err.line, 3);
+    }
+}
@@ -0,0 +1,257 @@
+use std::cell::RefCell;
+
+use slider_path::{cumulative_lengths, point_at_distance};
+
+/*
+  Control points are split into several bezier curves at repeated points
+  (red anchors in editor). Every curve is flattened with de Casteljau subdivision
+  until its pieces are flat enough to be drawn as lines.
+*/
+
+/*
+  Flatness of curve pieces in osu!pixels as in osu!lazer, second differences
+  of control points of flat piece are at most 2 * TOLERANCE. Piece of degree n
+  then deviates from its line by at most n(n-1)/8 of that.
+*/
+const TOLERANCE: f32 = 0.25;
+//Limits subdivision of degenerate curves
+const MAX_DEPTH: u32 = 16;
+
+//Control points of one curve, halves of it after subdivision
+type Curve = Vec<(f32, f32)>;
+
+//Flattened curve with distance along it to every point
+#[derive(Default, Debug)]
+struct Interpolated {
+    points: Vec<(f32, f32)>,
+    cumulative: Vec<f32>,
+    length: f32,
+}
+
+#[derive(Default, Debug)]
+pub struct Bezier {
+    vertex_list: Vec<(f32, f32)>,
+    //built on first use and dropped when vertices change
+    interpolated: RefCell<Option<Interpolated>>,
+}
+
+impl Bezier {
+    pub fn new() -> Bezier {
+        Bezier { ..Default::default() }
+    }
+
+    pub fn clear(&mut self) {
+        self.vertex_list.clear();
+        *self.interpolated.borrow_mut() = None;
+    }
+
+    pub fn add_vertexi(&mut self, point:(i32, i32)) {
+        self.add_vertexf( (point.0 as f32, point.1 as f32) );
+    }
+
+    pub fn add_vertexf(&mut self, point:(f32, f32)) {
+        self.vertex_list.push(point);
+        *self.interpolated.borrow_mut() = None;
+    }
+
+    pub fn get_tesselated_points(&mut self) -> Vec<(f32, f32)> {
+        self.interpolate();
+        self.interpolated.borrow().as_ref().map_or(Vec::new(), |i| i.points.clone())
+    }
+
+    //Control points of every curve between red anchors
+    pub fn segments(&self) -> Vec<Vec<(f32, f32)>> {
+        let mut segments = Vec::new();
+        let mut current:Vec<(f32, f32)> = Vec::new();
+        for p in &self.vertex_list {
+            if current.last() == Some(p) {
+                segments.push(current);
+                current = Vec::new();
+            }
+            current.push(*p);
+        }
+        segments.push(current);
+        segments.into_iter().filter(|s| s.len() > 1).collect()
+    }
+
+    fn interpolate(&self) {
+        if self.interpolated.borrow().is_some() {
+            return;
+        }
+        let mut points = Vec::new();
+        for segment in self.segments() {
+            flatten(&segment, &mut points);
+        }
+        if points.is_empty() {
+            if let Some(p) = self.vertex_list.first() {
+                points.push(*p);
+            }
+        }
+        let cumulative = cumulative_lengths(&points);
+        let length = cumulative.last().cloned().unwrap_or(0.0);
+        *self.interpolated.borrow_mut() = Some(Interpolated { points: points, cumulative: cumulative, length: length });
+    }
+
+    //Point at k of curve length, so equal steps of k are equal distances along the curve
+    #[allow(clippy::result_unit_err)] //public since the first version of map code
+    pub fn value(&self, k: f32) -> Result<(f32, f32), ()> {
+        if self.vertex_list.is_empty() || !(0.00..=1.00).contains(&k) {
+            Err( () )
+        } else {
+            self.interpolate();
+            let interpolated = self.interpolated.borrow();
+            let curve = interpolated.as_ref().unwrap();
+            Ok( point_at_distance(&curve.points, &curve.cumulative, curve.length * k) )
+        }
+    }
+
+    pub fn approx_length(&self) -> f32 {
+        let mut length = 0.00;
+        let mut p2:(f32, f32) = (0.0, 0.0);
+        for i in 0..self.vertex_list.len() {
+            let p = self.vertex_list[i];
+            if i > 0 {
+                length += ((p.0 - p2.0)*(p.0 - p2.0) + (p.1 - p2.1)*(p.1 - p2.1)).sqrt();
+            }
+            p2 = p;
+        }
+        length
+    }
+}
+
+/*
+  Appends points of single bezier curve to out.
+  First point of curve is not repeated if out already ends with it.
+*/
+fn flatten(control: &[(f32, f32)], out: &mut Vec<(f32, f32)>) {
+    if out.last() != control.first() {
+        out.push(control[0]);
+    }
+    //pieces are popped left first, so points come in order
+    let mut stack = vec![(control.to_vec(), 0)];
+    while let Some((piece, depth)) = stack.pop() {
+        if depth >= MAX_DEPTH || is_flat(&piece) {
+            out.push(piece[piece.len() - 1]);
+        } else {
+            let (left, right) = subdivide(&piece);
+            stack.push((right, depth + 1));
+            stack.push((left, depth + 1));
+        }
+    }
+}
+
+//Second differences of control points are small enough
+fn is_flat(control: &[(f32, f32)]) -> bool {
+    for i in 1..control.len() - 1 {
+        let dx = control[i - 1].0 - 2.0 * control[i].0 + control[i + 1].0;
+        let dy = control[i - 1].1 - 2.0 * control[i].1 + control[i + 1].1;
+        if dx * dx + dy * dy > TOLERANCE * TOLERANCE * 4.0 {
+            return false;
+        }
+    }
+    true
+}
+
+//de Casteljau split at t = 0.5
+fn subdivide(control: &[(f32, f32)]) -> (Curve, Curve) {
+    let n = control.len();
+    let mut mid = control.to_vec();
+    let mut left = Vec::with_capacity(n);
+    let mut right = vec![(0.0, 0.0); n];
+    for i in 0..n {
+        left.push(mid[0]);
+        right[n - i - 1] = mid[n - i - 1];
+        for j in 0..n - i - 1 {
+            mid[j] = ((mid[j].0 + mid[j + 1].0) / 2.0, (mid[j].1 + mid[j + 1].1) / 2.0);
+        }
+    }
+    (left, right)
+}
+
+#[test]
+fn spline_tesselate() {
+    let mut spline = Bezier::new();
+
+    spline.add_vertexf( (0.0, 0.0) );
+    spline.add_vertexf( (0.0, 100.0) );
+    spline.add_vertexf( (100.0, 0.0) );
+    spline.add_vertexf( (100.0, 100.0) );
+
+    let points = spline.get_tesselated_points();
+    assert_eq!(points[0], (0.0, 0.0));
+    assert_eq!(*points.last().unwrap(), (100.0, 100.0));
+
+    //every point of the real cubic curve is close to some line of flattened one,
+    //at most 3 * 2 / 8 * (2 * TOLERANCE) away
+    let distance_to_line = |p: (f32, f32), a: (f32, f32), b: (f32, f32)| {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = dx * dx + dy * dy;
+        let t = if len > 0.0 { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len).max(0.0).min(1.0) } else { 0.0 };
+        let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+        ((p.0 - x) * (p.0 - x) + (p.1 - y) * (p.1 - y)).sqrt()
+    };
+    for i in 0..101 {
+        let t = i as f32 / 100.0;
+        let nt = 1.0 - t;
+        let x = 3.0 * nt * t * t * 100.0 + t * t * t * 100.0;
+        let y = 3.0 * nt * nt * t * 100.0 + t * t * t * 100.0;
+        let nearest = points.windows(2).map(|w| distance_to_line((x, y), w[0], w[1])).fold(f32::MAX, f32::min);
+        assert!(nearest <= TOLERANCE * 1.5, "{} is {} away", t, nearest);
+    }
+
+    //repeated point starts a new curve, which is a straight line here
+    spline.add_vertexf( (100.0, 100.0) );
+    spline.add_vertexf( (200.0, 100.0) );
+    assert_eq!(spline.segments().len(), 2);
+    let split = spline.get_tesselated_points();
+    assert_eq!(&split[..points.len()], &points[..]);
+    assert_eq!(&split[points.len()..], &[(200.0, 100.0)]);
+}
+
+#[test]
+fn spline_follows_curve() {
+    let mut spline = Bezier::new();
+    spline.add_vertexi( (0, 0) );
+    spline.add_vertexi( (50, 100) );
+    spline.add_vertexi( (100, 0) );
+    let points = spline.get_tesselated_points();
+
+    assert_eq!(points[0], (0.0, 0.0));
+    assert_eq!(*points.last().unwrap(), (100.0, 0.0));
+    //quadratic curve is y = 2x - x^2 / 50, all points lie on it
+    for p in &points {
+        assert!((p.1 - (2.0 * p.0 - p.0 * p.0 / 50.0)).abs() < TOLERANCE * 2.0);
+    }
+    assert!(points.iter().any(|p| (p.0 - 50.0).abs() < 0.01 && (p.1 - 50.0).abs() < 0.01));
+}
+
+#[test]
+fn spline_red_anchors() {
+    let mut spline = Bezier::new();
+    spline.add_vertexi( (0, 0) );
+    spline.add_vertexi( (100, 0) );
+    spline.add_vertexi( (100, 0) );
+    spline.add_vertexi( (100, 100) );
+    assert_eq!(spline.segments().len(), 2);
+
+    //two straight lines with the corner at anchor
+    let points = spline.get_tesselated_points();
+    assert_eq!(points, vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)]);
+    assert_eq!(spline.interpolated.borrow().as_ref().unwrap().length, 200.0);
+    assert_eq!(spline.value(0.25), Ok((50.0, 0.0)));
+    assert_eq!(spline.value(0.75), Ok((100.0, 50.0)));
+}
+
+#[test]
+fn spline_value_of_line() {
+    let mut spline = Bezier::new();
+    spline.add_vertexi( (0, 0) );
+    spline.add_vertexi( (100, 0) );
+    assert_eq!(spline.value(0.5), Ok((50.0, 0.0)));
+    assert_eq!(spline.value(1.5), Err(()));
+
+    //cached curve is rebuilt for new vertex
+    spline.add_vertexi( (100, 0) );
+    spline.add_vertexi( (100, 100) );
+    assert_eq!(spline.value(1.0), Ok((100.0, 100.0)));
+}
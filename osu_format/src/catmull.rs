@@ -0,0 +1,95 @@
+/*
+  Catmull-Rom spline of old "C" sliders.
+  Curve goes through every control point, each span between two of them
+  is split into the same number of pieces as in osu!.
+*/
+
+const DETAIL: i32 = 50;
+
+#[derive(Default, Debug)]
+pub struct Catmull {
+    vertex_list: Vec<(f32, f32)>,
+    interpolated_list: Vec<(f32, f32)>,
+    interpolated: bool,
+}
+
+impl Catmull {
+    pub fn new() -> Catmull {
+        Catmull { interpolated: false, ..Default::default() }
+    }
+
+    pub fn add_vertexi(&mut self, point:(i32, i32)) {
+        self.vertex_list.push( (point.0 as f32, point.1 as f32) );
+    }
+
+    pub fn add_vertexf(&mut self, point:(f32, f32)) {
+        self.vertex_list.push(point);
+    }
+
+    pub fn get_tesselated_points(&mut self) -> Vec<(f32, f32)> {
+        if !self.interpolated {
+            self.interpolate();
+        }
+        self.interpolated_list.clone()
+    }
+
+    fn interpolate(&mut self) {
+        self.interpolated_list.clear();
+        let points = &self.vertex_list;
+        let n = points.len();
+        if n > 0 {
+            self.interpolated_list.push(points[0]);
+        }
+        for i in 0..n.saturating_sub(1) {
+            //missing neighbours at the ends are mirrored
+            let v1 = if i > 0 { points[i - 1] } else { points[i] };
+            let v2 = points[i];
+            let v3 = points[i + 1];
+            let v4 = if i + 2 < n { points[i + 2] } else { (v3.0 * 2.0 - v2.0, v3.1 * 2.0 - v2.1) };
+            for c in 1..DETAIL + 1 {
+                let point = find_point(v1, v2, v3, v4, c as f32 / DETAIL as f32);
+                self.interpolated_list.push(point);
+            }
+        }
+        self.interpolated = true;
+    }
+}
+
+fn find_point(v1: (f32, f32), v2: (f32, f32), v3: (f32, f32), v4: (f32, f32), t: f32) -> (f32, f32) {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let axis = |a: f32, b: f32, c: f32, d: f32| {
+        0.5 * (2.0 * b + (c - a) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 + (3.0 * b - a - 3.0 * c + d) * t3)
+    };
+    (axis(v1.0, v2.0, v3.0, v4.0), axis(v1.1, v2.1, v3.1, v4.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catmull_tesselate() {
+        let mut curve = Catmull::new();
+        curve.add_vertexi( (0, 0) );
+        curve.add_vertexi( (100, 100) );
+        curve.add_vertexi( (200, 0) );
+        let points = curve.get_tesselated_points();
+
+        assert_eq!(points.len(), 1 + 2 * DETAIL as usize);
+        //goes through every control point
+        assert_eq!(points[0], (0.0, 0.0));
+        assert_eq!(points[DETAIL as usize], (100.0, 100.0));
+        assert_eq!(points[2 * DETAIL as usize], (200.0, 0.0));
+    }
+
+    #[test]
+    fn catmull_two_points_is_line() {
+        let mut curve = Catmull::new();
+        curve.add_vertexi( (0, 0) );
+        curve.add_vertexi( (100, 50) );
+        for p in curve.get_tesselated_points() {
+            assert!((p.1 - p.0 / 2.0).abs() < 0.01);
+        }
+    }
+}
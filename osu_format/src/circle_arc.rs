@@ -0,0 +1,163 @@
+use std::f64::consts::PI;
+
+use bezier::Bezier;
+
+/*
+  Arc of the circle going through 3 points, used by "P" sliders.
+  Points are placed with the same angle step, so they are evenly spaced along the arc.
+  If there are not exactly 3 points or they lie on one line, curve is bezier as in osu!.
+*/
+
+//Max distance in osu!pixels between the arc and the chord of two neighbour points
+const TOLERANCE: f64 = 0.1;
+
+#[derive(Default, Debug)]
+pub struct CircleArc {
+    vertex_list: Vec<(f32, f32)>,
+    interpolated_list: Vec<(f32, f32)>,
+    interpolated: bool,
+}
+
+impl CircleArc {
+    pub fn new() -> CircleArc {
+        CircleArc { interpolated: false, ..Default::default() }
+    }
+
+    pub fn add_vertexi(&mut self, point:(i32, i32)) {
+        self.vertex_list.push( (point.0 as f32, point.1 as f32) );
+    }
+
+    pub fn add_vertexf(&mut self, point:(f32, f32)) {
+        self.vertex_list.push(point);
+    }
+
+    pub fn get_tesselated_points(&mut self) -> Vec<(f32, f32)> {
+        if !self.interpolated {
+            self.interpolated_list = match self.arc() {
+                Some(points) => points,
+                None => {
+                    let mut curve = Bezier::new();
+                    for p in &self.vertex_list {
+                        curve.add_vertexf(*p);
+                    }
+                    curve.get_tesselated_points()
+                }
+            };
+            self.interpolated = true;
+        }
+        self.interpolated_list.clone()
+    }
+
+    //None when arc can't be built
+    fn arc(&self) -> Option<Vec<(f32, f32)>> {
+        if self.vertex_list.len() != 3 {
+            return None;
+        }
+        let a = (self.vertex_list[0].0 as f64, self.vertex_list[0].1 as f64);
+        let b = (self.vertex_list[1].0 as f64, self.vertex_list[1].1 as f64);
+        let c = (self.vertex_list[2].0 as f64, self.vertex_list[2].1 as f64);
+
+        //circumscribed circle via barycentric coordinates of its centre
+        let a_sq = dist_sq(b, c);
+        let b_sq = dist_sq(a, c);
+        let c_sq = dist_sq(a, b);
+        if a_sq < 1e-3 || b_sq < 1e-3 || c_sq < 1e-3 {
+            return None;
+        }
+        let s = a_sq * (b_sq + c_sq - a_sq);
+        let t = b_sq * (a_sq + c_sq - b_sq);
+        let u = c_sq * (a_sq + b_sq - c_sq);
+        let sum = s + t + u;
+        if sum.abs() < 1e-3 {
+            //collinear
+            return None;
+        }
+        let centre = ((s * a.0 + t * b.0 + u * c.0) / sum, (s * a.1 + t * b.1 + u * c.1) / sum);
+
+        let radius = dist_sq(a, centre).sqrt();
+        let theta_start = (a.1 - centre.1).atan2(a.0 - centre.0);
+        let mut theta_end = (c.1 - centre.1).atan2(c.0 - centre.0);
+        while theta_end < theta_start {
+            theta_end += 2.0 * PI;
+        }
+
+        //going counter-clockwise from a to c must pass b, otherwise go the other way
+        let mut direction = 1.0;
+        let mut theta_range = theta_end - theta_start;
+        let ortho = (c.1 - a.1, -(c.0 - a.0));
+        if ortho.0 * (b.0 - a.0) + ortho.1 * (b.1 - a.1) < 0.0 {
+            direction = -1.0;
+            theta_range = 2.0 * PI - theta_range;
+        }
+
+        let segments = if 2.0 * radius <= TOLERANCE {
+            1
+        } else {
+            ((theta_range / (2.0 * (1.0 - TOLERANCE / radius).acos())).ceil() as usize).max(1)
+        };
+        let mut points = Vec::with_capacity(segments + 1);
+        for i in 0..segments + 1 {
+            let theta = theta_start + direction * theta_range * i as f64 / segments as f64;
+            points.push( ((centre.0 + radius * theta.cos()) as f32, (centre.1 + radius * theta.sin()) as f32) );
+        }
+        Some(points)
+    }
+}
+
+fn dist_sq(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dist(a: (f32, f32), b: (f32, f32)) -> f32 {
+        ((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt()
+    }
+
+    #[test]
+    fn arc_through_three_points() {
+        let mut arc = CircleArc::new();
+        arc.add_vertexi( (0, 0) );
+        arc.add_vertexi( (50, 50) );
+        arc.add_vertexi( (100, 0) );
+        let points = arc.get_tesselated_points();
+
+        assert!(dist(points[0], (0.0, 0.0)) < 0.01);
+        assert!(dist(*points.last().unwrap(), (100.0, 0.0)) < 0.01);
+        //half of circle with centre at (50, 0) going through (50, 50)
+        for p in &points {
+            assert!((dist(*p, (50.0, 0.0)) - 50.0).abs() < 0.01);
+            assert!(p.1 >= -0.01);
+        }
+        let step = dist(points[0], points[1]);
+        for i in 1..points.len() {
+            assert!((dist(points[i - 1], points[i]) - step).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn arc_direction() {
+        let mut arc = CircleArc::new();
+        arc.add_vertexi( (0, 0) );
+        arc.add_vertexi( (50, -50) );
+        arc.add_vertexi( (100, 0) );
+        let points = arc.get_tesselated_points();
+        assert!(points[points.len() / 2].1 < -49.0);
+    }
+
+    #[test]
+    fn collinear_falls_back_to_bezier() {
+        let mut arc = CircleArc::new();
+        arc.add_vertexi( (0, 0) );
+        arc.add_vertexi( (50, 0) );
+        arc.add_vertexi( (100, 0) );
+        let points = arc.get_tesselated_points();
+        assert!(points.len() > 1);
+        for p in &points {
+            assert!(p.1.abs() < 0.01);
+        }
+        assert!(dist(*points.last().unwrap(), (100.0, 0.0)) < 0.01);
+    }
+}
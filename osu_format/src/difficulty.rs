@@ -0,0 +1,159 @@
+use hit_object::circle_scale;
+
+/*
+  Gameplay values derived from difficulty settings of beatmap, as in osu!.
+  Most of them are interpolated between values at 0, 5 and 10.
+*/
+
+//Radius of circle with scale 1 in osu!pixels
+pub const OBJECT_RADIUS: f32 = 64.0;
+//Shortest preempt, reached at AR 10
+pub const PREEMPT_MIN: f32 = 450.0;
+//Objects hit later than this are missed
+pub const MISS_WINDOW: f32 = 400.0;
+//Fastest spinning counted, 477 rpm
+pub const MAX_ROTATIONS_PER_SECOND: f32 = 477.0 / 60.0;
+//Spins after required ones which don't give bonus
+const BONUS_SPINS_GAP: i32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DifficultyAttributes {
+    pub circle_size: f32,
+    pub approach_rate: f32,
+    pub overall_difficulty: f32,
+    pub hp_drain: f32,
+    //Speed of playback, AR and OD above are already converted to real time
+    pub clock_rate: f32,
+}
+
+impl Default for DifficultyAttributes {
+    fn default() -> DifficultyAttributes {
+        DifficultyAttributes::new(5.0, 5.0, 5.0, 5.0)
+    }
+}
+
+impl DifficultyAttributes {
+    pub fn new(circle_size: f32, approach_rate: f32, overall_difficulty: f32, hp_drain: f32) -> DifficultyAttributes {
+        DifficultyAttributes {
+            circle_size: circle_size,
+            approach_rate: approach_rate,
+            overall_difficulty: overall_difficulty,
+            hp_drain: hp_drain,
+            clock_rate: 1.0,
+        }
+    }
+
+    /*
+      Same map played at other speed. Preempt and hit windows get shorter in real time,
+      AR and OD are changed to give them.
+    */
+    pub fn with_clock_rate(&self, clock_rate: f32) -> DifficultyAttributes {
+        let preempt = self.preempt() / clock_rate;
+        let approach_rate = if preempt > 1200.0 {
+            (1800.0 - preempt) / 120.0
+        } else {
+            (1200.0 - preempt) / 150.0 + 5.0
+        };
+        let hit_window = self.hit_window_300() / clock_rate;
+        DifficultyAttributes {
+            approach_rate: approach_rate,
+            overall_difficulty: (80.0 - hit_window) / 6.0,
+            clock_rate: self.clock_rate * clock_rate,
+            ..*self
+        }
+    }
+
+    pub fn circle_scale(&self) -> f32 {
+        circle_scale(self.circle_size)
+    }
+
+    //osu!pixels
+    pub fn circle_radius(&self) -> f32 {
+        OBJECT_RADIUS * self.circle_scale()
+    }
+
+    //ms before the object its approach circle appears
+    pub fn preempt(&self) -> f32 {
+        difficulty_range(self.approach_rate, 1800.0, 1200.0, PREEMPT_MIN)
+    }
+
+    //ms object takes to become fully visible
+    pub fn fade_in(&self) -> f32 {
+        400.0 * (self.preempt() / PREEMPT_MIN).min(1.0)
+    }
+
+    //Hit windows are ms before or after the object time
+    pub fn hit_window_300(&self) -> f32 {
+        difficulty_range(self.overall_difficulty, 80.0, 50.0, 20.0)
+    }
+
+    pub fn hit_window_100(&self) -> f32 {
+        difficulty_range(self.overall_difficulty, 140.0, 100.0, 60.0)
+    }
+
+    pub fn hit_window_50(&self) -> f32 {
+        difficulty_range(self.overall_difficulty, 200.0, 150.0, 100.0)
+    }
+
+    pub fn hit_window_miss(&self) -> f32 {
+        MISS_WINDOW
+    }
+
+    pub fn hp_drain_rate(&self) -> f32 {
+        self.hp_drain
+    }
+
+    //Lowest health player with perfect play should have at the end of map, 0..1
+    pub fn target_minimum_health(&self) -> f32 {
+        difficulty_range(self.hp_drain, 0.99, 0.9, 0.4)
+    }
+
+    //Full rotations needed to clear spinner of duration in ms
+    pub fn spins_required(&self, duration: i32) -> i32 {
+        let seconds = duration.max(0) as f32 / 1000.0;
+        (seconds * difficulty_range(self.overall_difficulty, 1.5, 2.5, 3.75)) as i32
+    }
+
+    //Rotations giving bonus score after required ones
+    pub fn max_bonus_spins(&self, duration: i32) -> i32 {
+        let seconds = duration.max(0) as f32 / 1000.0;
+        ((seconds * MAX_ROTATIONS_PER_SECOND) as i32 - self.spins_required(duration) - BONUS_SPINS_GAP).max(0)
+    }
+}
+
+//Value for difficulty 0..10, linear between min at 0, mid at 5 and max at 10
+pub fn difficulty_range(difficulty: f32, min: f32, mid: f32, max: f32) -> f32 {
+    if difficulty > 5.0 {
+        mid + (max - mid) * (difficulty - 5.0) / 5.0
+    } else if difficulty < 5.0 {
+        mid - (mid - min) * (5.0 - difficulty) / 5.0
+    } else {
+        mid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_values() {
+        let attrs = DifficultyAttributes::new(4.0, 9.0, 8.0, 6.0);
+        assert!((attrs.circle_radius() - 36.48).abs() < 0.001);
+        assert_eq!(attrs.preempt(), 600.0);
+        assert_eq!(attrs.fade_in(), 400.0);
+        assert!((attrs.hit_window_300() - 32.0).abs() < 0.001);
+        assert!((attrs.hit_window_100() - 76.0).abs() < 0.001);
+        assert!((attrs.hit_window_50() - 120.0).abs() < 0.001);
+
+        let fast = DifficultyAttributes::new(5.0, 10.0, 5.0, 5.0);
+        assert_eq!(fast.preempt(), 450.0);
+        let slow = DifficultyAttributes::new(5.0, 0.0, 0.0, 5.0);
+        assert_eq!(slow.preempt(), 1800.0);
+        assert_eq!(slow.hit_window_50(), 200.0);
+
+        //2 s spinner at OD 5 needs 2.5 rotations per second
+        assert_eq!(fast.spins_required(2000), 5);
+        assert_eq!(fast.max_bonus_spins(2000), 8);
+    }
+}
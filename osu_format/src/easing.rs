@@ -0,0 +1,198 @@
+use std::f64::consts::PI;
+
+/*
+    Easing functions of storyboard commands.
+    Order matters: index in .osu/.osb files is the position in this enum.
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    Out,
+    In,
+    InQuad,
+    OutQuad,
+    InOutQuad,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    InQuart,
+    OutQuart,
+    InOutQuart,
+    InQuint,
+    OutQuint,
+    InOutQuint,
+    InSine,
+    OutSine,
+    InOutSine,
+    InExpo,
+    OutExpo,
+    InOutExpo,
+    InCirc,
+    OutCirc,
+    InOutCirc,
+    InElastic,
+    OutElastic,
+    OutElasticHalf,
+    OutElasticQuarter,
+    InOutElastic,
+    InBack,
+    OutBack,
+    InOutBack,
+    InBounce,
+    OutBounce,
+    InOutBounce,
+}
+
+impl Default for Easing {
+    fn default() -> Easing { Easing::Linear }
+}
+
+const EASINGS: [Easing; 35] = [
+    Easing::Linear, Easing::Out, Easing::In,
+    Easing::InQuad, Easing::OutQuad, Easing::InOutQuad,
+    Easing::InCubic, Easing::OutCubic, Easing::InOutCubic,
+    Easing::InQuart, Easing::OutQuart, Easing::InOutQuart,
+    Easing::InQuint, Easing::OutQuint, Easing::InOutQuint,
+    Easing::InSine, Easing::OutSine, Easing::InOutSine,
+    Easing::InExpo, Easing::OutExpo, Easing::InOutExpo,
+    Easing::InCirc, Easing::OutCirc, Easing::InOutCirc,
+    Easing::InElastic, Easing::OutElastic, Easing::OutElasticHalf, Easing::OutElasticQuarter, Easing::InOutElastic,
+    Easing::InBack, Easing::OutBack, Easing::InOutBack,
+    Easing::InBounce, Easing::OutBounce, Easing::InOutBounce,
+];
+
+const ELASTIC_CONST: f64 = 2.0 * PI / 0.3;
+const ELASTIC_CONST2: f64 = 0.3 / 4.0;
+const BACK_CONST: f64 = 1.70158;
+const BACK_CONST2: f64 = BACK_CONST * 1.525;
+
+impl Easing {
+    pub fn from_index(index: i32) -> Option<Easing> {
+        if index >= 0 && (index as usize) < EASINGS.len() {
+            Some(EASINGS[index as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn index(&self) -> i32 {
+        EASINGS.iter().position(|e| e == self).unwrap() as i32
+    }
+
+    //Maps linear progress 0..1 to eased progress. Elastic and back easings may leave 0..1
+    pub fn apply(&self, progress: f32) -> f32 {
+        let t = progress as f64;
+        let v = match *self {
+            Easing::Linear => t,
+            Easing::In | Easing::InQuad => t * t,
+            Easing::Out | Easing::OutQuad => t * (2.0 - t),
+            Easing::InOutQuad => {
+                if t < 0.5 { t * t * 2.0 } else { let t = t - 1.0; t * t * -2.0 + 1.0 }
+            }
+            Easing::InCubic => t * t * t,
+            Easing::OutCubic => { let t = t - 1.0; t * t * t + 1.0 }
+            Easing::InOutCubic => {
+                if t < 0.5 { t * t * t * 4.0 } else { let t = t - 1.0; t * t * t * 4.0 + 1.0 }
+            }
+            Easing::InQuart => t.powi(4),
+            Easing::OutQuart => 1.0 - (t - 1.0).powi(4),
+            Easing::InOutQuart => {
+                if t < 0.5 { t.powi(4) * 8.0 } else { (t - 1.0).powi(4) * -8.0 + 1.0 }
+            }
+            Easing::InQuint => t.powi(5),
+            Easing::OutQuint => (t - 1.0).powi(5) + 1.0,
+            Easing::InOutQuint => {
+                if t < 0.5 { t.powi(5) * 16.0 } else { (t - 1.0).powi(5) * 16.0 + 1.0 }
+            }
+            Easing::InSine => 1.0 - (t * PI / 2.0).cos(),
+            Easing::OutSine => (t * PI / 2.0).sin(),
+            Easing::InOutSine => 0.5 - 0.5 * (PI * t).cos(),
+            Easing::InExpo => 2.0f64.powf(10.0 * (t - 1.0)),
+            Easing::OutExpo => -(2.0f64.powf(-10.0 * t)) + 1.0,
+            Easing::InOutExpo => {
+                if t < 0.5 {
+                    0.5 * 2.0f64.powf(20.0 * t - 10.0)
+                } else {
+                    1.0 - 0.5 * 2.0f64.powf(-20.0 * t + 10.0)
+                }
+            }
+            Easing::InCirc => 1.0 - (1.0 - t * t).sqrt(),
+            Easing::OutCirc => { let t = t - 1.0; (1.0 - t * t).sqrt() }
+            Easing::InOutCirc => {
+                let t = t * 2.0;
+                if t < 1.0 {
+                    0.5 - 0.5 * (1.0 - t * t).sqrt()
+                } else {
+                    let t = t - 2.0;
+                    0.5 * (1.0 - t * t).sqrt() + 0.5
+                }
+            }
+            Easing::InElastic => {
+                -(2.0f64.powf(-10.0 + 10.0 * t)) * ((1.0 - ELASTIC_CONST2 - t) * ELASTIC_CONST).sin()
+            }
+            Easing::OutElastic => {
+                2.0f64.powf(-10.0 * t) * ((t - ELASTIC_CONST2) * ELASTIC_CONST).sin() + 1.0
+            }
+            Easing::OutElasticHalf => {
+                2.0f64.powf(-10.0 * t) * ((0.5 * t - ELASTIC_CONST2) * ELASTIC_CONST).sin() + 1.0
+            }
+            Easing::OutElasticQuarter => {
+                2.0f64.powf(-10.0 * t) * ((0.25 * t - ELASTIC_CONST2) * ELASTIC_CONST).sin() + 1.0
+            }
+            Easing::InOutElastic => {
+                let t = t * 2.0;
+                if t < 1.0 {
+                    -0.5 * 2.0f64.powf(-10.0 + 10.0 * t) * ((1.0 - ELASTIC_CONST2 * 1.5 - t) * ELASTIC_CONST / 1.5).sin()
+                } else {
+                    let t = t - 1.0;
+                    0.5 * 2.0f64.powf(-10.0 * t) * ((t - ELASTIC_CONST2 * 1.5) * ELASTIC_CONST / 1.5).sin() + 1.0
+                }
+            }
+            Easing::InBack => t * t * ((BACK_CONST + 1.0) * t - BACK_CONST),
+            Easing::OutBack => { let t = t - 1.0; t * t * ((BACK_CONST + 1.0) * t + BACK_CONST) + 1.0 }
+            Easing::InOutBack => {
+                let t = t * 2.0;
+                if t < 1.0 {
+                    0.5 * t * t * ((BACK_CONST2 + 1.0) * t - BACK_CONST2)
+                } else {
+                    let t = t - 2.0;
+                    0.5 * (t * t * ((BACK_CONST2 + 1.0) * t + BACK_CONST2) + 2.0)
+                }
+            }
+            Easing::InBounce => 1.0 - out_bounce(1.0 - t),
+            Easing::OutBounce => out_bounce(t),
+            Easing::InOutBounce => {
+                if t < 0.5 { 0.5 - 0.5 * out_bounce(1.0 - t * 2.0) } else { out_bounce((t - 0.5) * 2.0) * 0.5 + 0.5 }
+            }
+        };
+        v as f32
+    }
+}
+
+fn out_bounce(t: f64) -> f64 {
+    if t < 1.0 / 2.75 {
+        7.5625 * t * t
+    } else if t < 2.0 / 2.75 {
+        let t = t - 1.5 / 2.75;
+        7.5625 * t * t + 0.75
+    } else if t < 2.5 / 2.75 {
+        let t = t - 2.25 / 2.75;
+        7.5625 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / 2.75;
+        7.5625 * t * t + 0.984375
+    }
+}
+
+#[test]
+fn easing_bounds() {
+    for i in 0..35 {
+        let easing = Easing::from_index(i).unwrap();
+        assert_eq!(easing.index(), i);
+        assert!(easing.apply(0.0).abs() < 0.001, "{:?} at 0", easing);
+        assert!((easing.apply(1.0) - 1.0).abs() < 0.001, "{:?} at 1", easing);
+    }
+    assert!(Easing::from_index(35).is_none());
+    assert_eq!(Easing::OutQuad.apply(0.5), 0.75);
+    assert_eq!(Easing::InQuad.apply(0.5), 0.25);
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use beatmap::SectionType;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    //first line is not "osu file format vN"
+    MissingHeader,
+    //line has less fields than its section requires
+    MissingField,
+    //field is present but can't be converted into its type
+    InvalidValue,
+    //stream failed or is not UTF-8, text has the reason
+    Io,
+}
+
+/*
+    Describes a single bad line of .osu file.
+    In lenient mode the same type is used for collected warnings.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatmapParseError {
+    pub kind: ParseErrorKind,
+    pub section: Option<SectionType>,
+    //1-based, as in text editors
+    pub line: usize,
+    pub field: &'static str,
+    pub text: String,
+}
+
+impl BeatmapParseError {
+    pub fn new(kind: ParseErrorKind, field: &'static str, text: &str) -> BeatmapParseError {
+        BeatmapParseError {
+            kind: kind,
+            section: None,
+            line: 0,
+            field: field,
+            text: text.to_string(),
+        }
+    }
+
+    pub fn at(mut self, section: Option<SectionType>, line: usize) -> BeatmapParseError {
+        self.section = section;
+        self.line = line;
+        self
+    }
+}
+
+impl fmt::Display for BeatmapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.section {
+            Some(section) => try!(write!(f, "line {} [{:?}]: ", self.line, section)),
+            None => try!(write!(f, "line {}: ", self.line)),
+        }
+        match self.kind {
+            ParseErrorKind::MissingHeader => write!(f, "expected file format header, got \"{}\"", self.text),
+            ParseErrorKind::MissingField => write!(f, "missing field `{}` in \"{}\"", self.field, self.text),
+            ParseErrorKind::InvalidValue => write!(f, "invalid value for `{}`: \"{}\"", self.field, self.text),
+            ParseErrorKind::Io => write!(f, "can't read {}: {}", self.field, self.text),
+        }
+    }
+}
+
+impl Error for BeatmapParseError {
+    fn description(&self) -> &str {
+        match self.kind {
+            ParseErrorKind::MissingHeader => "missing file format header",
+            ParseErrorKind::MissingField => "missing field",
+            ParseErrorKind::InvalidValue => "invalid field value",
+            ParseErrorKind::Io => "read error",
+        }
+    }
+}
+
+pub fn parse_field<T: FromStr>(field: &'static str, text: &str) -> Result<T, BeatmapParseError> {
+    text.trim().parse().map_err(|_| BeatmapParseError::new(ParseErrorKind::InvalidValue, field, text))
+}
+
+//Returns token at index or MissingField error for the whole line
+pub fn token<'a>(tokens: &[&'a str], index: usize, field: &'static str, line: &str) -> Result<&'a str, BeatmapParseError> {
+    match tokens.get(index) {
+        Some(t) => Ok(*t),
+        None => Err(BeatmapParseError::new(ParseErrorKind::MissingField, field, line)),
+    }
+}
@@ -0,0 +1,107 @@
+use error::*;
+
+/*
+    Typed lines of [Events] section.
+    Storyboard objects are not handled there.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Background { filename: String, x_offset: i32, y_offset: i32 },
+    Video { start_time: i32, filename: String, x_offset: i32, y_offset: i32 },
+    Break { start_time: i32, end_time: i32 },
+}
+
+impl Event {
+    //Returns None for lines which are not background, video or break
+    pub fn from_osu(line: &str) -> Result<Option<Event>, BeatmapParseError> {
+        let tokens = split_quoted(line);
+        match tokens[0].as_ref() {
+            "0" | "Background" => {
+                let filename = try!(token_string(&tokens, 2, "filename", line));
+                let (x, y) = try!(offsets(&tokens));
+                Ok(Some(Event::Background { filename: filename, x_offset: x, y_offset: y }))
+            }
+            "1" | "Video" => {
+                let start_time = try!(parse_field("startTime", &try!(token_string(&tokens, 1, "startTime", line))));
+                let filename = try!(token_string(&tokens, 2, "filename", line));
+                let (x, y) = try!(offsets(&tokens));
+                Ok(Some(Event::Video { start_time: start_time, filename: filename, x_offset: x, y_offset: y }))
+            }
+            "2" | "Break" => {
+                let start_time = try!(parse_field("startTime", &try!(token_string(&tokens, 1, "startTime", line))));
+                let end_time = try!(parse_field("endTime", &try!(token_string(&tokens, 2, "endTime", line))));
+                Ok(Some(Event::Break { start_time: start_time, end_time: end_time }))
+            }
+            _ => Ok(None)
+        }
+    }
+
+    pub fn to_osu(&self) -> String {
+        match *self {
+            Event::Background { ref filename, x_offset, y_offset } => {
+                format!("0,0,\"{}\",{},{}", filename, x_offset, y_offset)
+            }
+            Event::Video { start_time, ref filename, x_offset, y_offset } => {
+                format!("Video,{},\"{}\",{},{}", start_time, filename, x_offset, y_offset)
+            }
+            Event::Break { start_time, end_time } => {
+                format!("2,{},{}", start_time, end_time)
+            }
+        }
+    }
+}
+
+fn token_string(tokens: &[String], index: usize, field: &'static str, line: &str) -> Result<String, BeatmapParseError> {
+    match tokens.get(index) {
+        Some(t) => Ok(t.clone()),
+        None => Err(BeatmapParseError::new(ParseErrorKind::MissingField, field, line)),
+    }
+}
+
+//x and y offsets are optional and default to 0
+fn offsets(tokens: &[String]) -> Result<(i32, i32), BeatmapParseError> {
+    let mut offset = (0, 0);
+    if tokens.len() > 3 {
+        offset.0 = try!(parse_field("xOffset", &tokens[3]));
+    }
+    if tokens.len() > 4 {
+        offset.1 = try!(parse_field("yOffset", &tokens[4]));
+    }
+    Ok(offset)
+}
+
+//Splits by comma, but keeps commas inside quoted filenames. Quotes are removed.
+pub fn split_quoted(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => { quoted = !quoted }
+            ',' if !quoted => {
+                tokens.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => { current.push(c) }
+        }
+    }
+    tokens.push(current.trim().to_string());
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_events() {
+        assert_eq!(Event::from_osu("0,0,\"BG, final.jpg\",0,-20").unwrap(),
+            Some(Event::Background { filename: "BG, final.jpg".to_string(), x_offset: 0, y_offset: -20 }));
+        assert_eq!(Event::from_osu("Video,1903,\"intro.avi\"").unwrap(),
+            Some(Event::Video { start_time: 1903, filename: "intro.avi".to_string(), x_offset: 0, y_offset: 0 }));
+        assert_eq!(Event::from_osu("2,10000,15000").unwrap(),
+            Some(Event::Break { start_time: 10000, end_time: 15000 }));
+        assert_eq!(Event::from_osu("3,100,163,162,255").unwrap(), None);
+        assert!(Event::from_osu("Break,10000").is_err());
+    }
+}
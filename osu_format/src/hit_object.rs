@@ -0,0 +1,457 @@
+use slider_path::SliderPath;
+use beatmap::SampleSet;
+use error::*;
+
+pub const MAX_X:i32 = 512;
+pub const MAX_Y:i32 = 384;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum HitObjectType {
+    Circle,
+    Spinner,
+	Slider,
+    //osu!mania hold note, has end time as spinner
+    Hold,
+}
+
+impl Default for HitObjectType {
+    fn default() -> HitObjectType { HitObjectType::Circle }
+}
+
+/*
+    Type field of hit object is a bitmask:
+    object type, new combo and how many combo colours to skip.
+*/
+pub const TYPE_CIRCLE: i32 = 1;
+pub const TYPE_SLIDER: i32 = 2;
+pub const TYPE_NEW_COMBO: i32 = 4;
+pub const TYPE_SPINNER: i32 = 8;
+pub const TYPE_COLOUR_SKIP: i32 = 16 | 32 | 64;
+pub const TYPE_MANIA_HOLD: i32 = 128;
+
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct HitObjectFlags {
+    bits: i32
+}
+
+impl HitObjectFlags {
+    pub fn from_bits(bits: i32) -> HitObjectFlags {
+        HitObjectFlags { bits: bits }
+    }
+
+    pub fn new(obj_type: HitObjectType, new_combo: bool, colour_skip: i32) -> HitObjectFlags {
+        let mut bits = match obj_type {
+            HitObjectType::Circle => TYPE_CIRCLE,
+            HitObjectType::Slider => TYPE_SLIDER,
+            HitObjectType::Spinner => TYPE_SPINNER,
+            HitObjectType::Hold => TYPE_MANIA_HOLD,
+        };
+        if new_combo {
+            bits |= TYPE_NEW_COMBO;
+        }
+        bits |= (colour_skip << 4) & TYPE_COLOUR_SKIP;
+        HitObjectFlags { bits: bits }
+    }
+
+    pub fn bits(&self) -> i32 {
+        self.bits
+    }
+
+    //None if no known type bit is set
+    pub fn object_type(&self) -> Option<HitObjectType> {
+        if self.bits & TYPE_SLIDER > 0 {
+            Some(HitObjectType::Slider)
+        } else if self.bits & TYPE_SPINNER > 0 {
+            Some(HitObjectType::Spinner)
+        } else if self.bits & TYPE_MANIA_HOLD > 0 {
+            Some(HitObjectType::Hold)
+        } else if self.bits & TYPE_CIRCLE > 0 {
+            Some(HitObjectType::Circle)
+        } else {
+            None
+        }
+    }
+
+    pub fn new_combo(&self) -> bool {
+        self.bits & TYPE_NEW_COMBO > 0
+    }
+
+    //0 to 7
+    pub fn colour_skip(&self) -> i32 {
+        (self.bits & TYPE_COLOUR_SKIP) >> 4
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum SliderType {
+    SliderNone,
+    SliderCatmul,
+	SliderBezier,
+	SliderLinear,
+	SliderPassthrough
+}
+
+impl Default for SliderType {
+    fn default() -> SliderType { SliderType::SliderNone }
+}
+
+impl SliderType {
+    pub fn letter(&self) -> &'static str {
+        match *self {
+            SliderType::SliderCatmul => "C",
+            SliderType::SliderBezier | SliderType::SliderNone => "B",
+            SliderType::SliderLinear => "L",
+            SliderType::SliderPassthrough => "P",
+        }
+    }
+}
+
+/*
+    Hitsound field is a bitmask, so whistle, finish and clap can play together.
+    Normal sound always plays, its bit is optional.
+*/
+pub const HITSOUND_NORMAL: i32 = 1;
+pub const HITSOUND_WHISTLE: i32 = 2;
+pub const HITSOUND_FINISH: i32 = 4;
+pub const HITSOUND_CLAP: i32 = 8;
+
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct HitSoundType {
+    bits: i32
+}
+
+impl HitSoundType {
+    pub fn from_bits(bits: i32) -> HitSoundType {
+        HitSoundType { bits: bits }
+    }
+
+    pub fn value(&self) -> i32 {
+        self.bits
+    }
+
+    pub fn whistle(&self) -> bool {
+        self.bits & HITSOUND_WHISTLE > 0
+    }
+
+    pub fn finish(&self) -> bool {
+        self.bits & HITSOUND_FINISH > 0
+    }
+
+    pub fn clap(&self) -> bool {
+        self.bits & HITSOUND_CLAP > 0
+    }
+}
+
+/*
+    normalSet:additionSet:index:volume:filename field.
+    Zero values mean "take it from timing point".
+*/
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct HitSample {
+    pub normal_set: SampleSet,
+    pub addition_set: SampleSet,
+    pub index: i32,
+    pub volume: i32,
+    //custom sample which replaces all others
+    pub filename: String,
+}
+
+impl HitSample {
+    //Missing trailing fields keep default values
+    pub fn from_osu(text: &str) -> Result<HitSample, BeatmapParseError> {
+        let mut sample = HitSample { ..Default::default() };
+        let mut tokens = text.split(':');
+        match tokens.next() {
+            Some(set) if !set.is_empty() => { sample.normal_set = SampleSet::from_index(try!(parse_field("normalSet", set))) }
+            _ => {}
+        }
+        if let Some(set) = tokens.next() {
+            sample.addition_set = SampleSet::from_index(try!(parse_field("additionSet", set)));
+        }
+        if let Some(index) = tokens.next() {
+            sample.index = try!(parse_field("index", index));
+        }
+        if let Some(volume) = tokens.next() {
+            sample.volume = try!(parse_field("volume", volume));
+        }
+        if let Some(filename) = tokens.next() {
+            sample.filename = filename.to_string();
+        }
+        Ok(sample)
+    }
+
+    pub fn to_osu(&self) -> String {
+        format!("{}:{}:{}:{}:{}", self.normal_set.index(), self.addition_set.index(), self.index, self.volume, self.filename)
+    }
+}
+
+//Longest slider distance in osu!pixels nested objects are generated for
+pub const MAX_SLIDER_LENGTH: f32 = 100000.0;
+//Slider end is judged this many ms before the real end, as in osu!stable
+pub const LEGACY_LAST_TICK_OFFSET: f32 = 36.0;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum NestedKind {
+    Head,
+    Tick,
+    Repeat,
+    //judgement of slider end, slightly before the tail
+    LastTick,
+    //real end of slider, where its last hitsound plays
+    Tail,
+}
+
+/*
+    Scorable part of slider. Progress is position along the path 0..1,
+    span is the slide it belongs to.
+*/
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct NestedObject {
+    pub kind: NestedKind,
+    pub time: f32,
+    pub position: (f32, f32),
+    pub progress: f32,
+    pub span: i32,
+}
+
+//Scale of objects for circle size, circle radius is 64 * scale osu!pixels
+pub fn circle_scale(circle_size: f32) -> f32 {
+    (1.0 - 0.7 * (circle_size - 5.0) / 5.0) / 2.0
+}
+
+//Note: Maybe i need to use generics?
+#[derive(Default, Debug, PartialEq, Clone)]
+pub struct HitObject {
+    pub obj_type: HitObjectType,
+    pub points: Vec<(i32, i32)>,
+    pub time_start: i32,
+    //Duration in ms for sliders, spinners and holds
+    pub length: i32,
+    //as set in file, see Beatmap for computed combos
+    pub new_combo: bool,
+    pub colour_skip: i32,
+    //last object of its combo
+    pub end_combo: bool,
+    //index in combo colours
+    pub combo_index: i32,
+    pub combo_number: i32,
+    pub slider_type: SliderType,
+    pub slider_repeats: i32,
+    //Length of slider in osu!pixels as set by mapper, 0 if missing
+    pub pixel_length: f32,
+    //Time of one slide in ms, computed from timing points
+    pub span_duration: f32,
+    pub path: SliderPath,
+    //Head, ticks, repeats and tail in time order, filled by Beatmap
+    pub nested: Vec<NestedObject>,
+    //Objects of stack are shifted up-left by their height, negative goes down-right
+    pub stack_height: i32,
+    //Hitsounds and (normal, addition) sets of slider head, repeats and tail
+    pub edge_sounds: Vec<HitSoundType>,
+    pub edge_sets: Vec<(SampleSet, SampleSet)>,
+
+    pub hitsound: HitSoundType,
+    pub hit_sample: HitSample
+}
+
+impl HitObject {
+    pub fn new(obj_type: HitObjectType) -> HitObject {
+        HitObject { obj_type: obj_type, combo_number: 1, ..Default::default() }
+    }
+
+    pub fn circle(position: (i32, i32), time_start: i32) -> HitObject {
+        let mut obj = HitObject::new(HitObjectType::Circle);
+        obj.time_start = time_start;
+        obj.points.push(position);
+        obj
+    }
+
+    //First point is the head. Duration and nested objects are filled by Beatmap
+    pub fn slider(slider_type: SliderType, points: Vec<(i32, i32)>, time_start: i32, repeats: i32, pixel_length: f32) -> HitObject {
+        let mut obj = HitObject::new(HitObjectType::Slider);
+        obj.time_start = time_start;
+        obj.slider_type = slider_type;
+        obj.points = points;
+        obj.slider_repeats = repeats;
+        obj.pixel_length = pixel_length;
+        obj.prepare_slider();
+        let edges = repeats.max(1) as usize + 1;
+        obj.edge_sounds = vec![obj.hitsound; edges];
+        obj.edge_sets = vec![(SampleSet::NONE, SampleSet::NONE); edges];
+        obj
+    }
+
+    //Spinners are always at the centre of playfield
+    pub fn spinner(time_start: i32, time_end: i32) -> HitObject {
+        let mut obj = HitObject::new(HitObjectType::Spinner);
+        obj.time_start = time_start;
+        obj.points.push((MAX_X / 2, MAX_Y / 2));
+        obj.with_spinner_length(time_end - time_start);
+        obj
+    }
+
+    pub fn with_params(&mut self, time_start: i32, end_combo: bool, hitsound: HitSoundType) {
+        self.time_start = time_start;
+        self.end_combo = end_combo;
+        self.hitsound = hitsound;
+    }
+
+    pub fn with_spinner_length(&mut self, length: i32) {
+        if self.obj_type == HitObjectType::Spinner {
+            self.length = length;
+        }
+    }
+
+    pub fn add_point(&mut self, point: (i32, i32)) {
+        if (self.obj_type != HitObjectType::Slider) && (!self.points.is_empty()) {}
+        else {
+            self.points.push(point);
+        }
+    }
+
+    //Missing or non-positive length keeps path as long as its control points make it
+    pub fn prepare_slider(&mut self) {
+        if self.obj_type == HitObjectType::Slider {
+            self.path = SliderPath::new(self.slider_type, &self.points, self.pixel_length);
+        }
+    }
+
+    //Length slider travels in one span, in osu!pixels
+    pub fn slider_length(&self) -> f32 {
+        if self.pixel_length > 0.0 { self.pixel_length } else { self.path.length() }
+    }
+
+    /*
+      Fills nested objects of slider. Velocity is in osu!pixels per ms,
+      ticks closer than 10 ms of travel to span end are dropped.
+    */
+    pub fn generate_nested(&mut self, velocity: f32, tick_distance: f32) {
+        self.nested.clear();
+        if self.obj_type != HitObjectType::Slider {
+            return;
+        }
+        let spans = self.slider_repeats.max(1);
+        let span_duration = self.span_duration;
+        let start = self.time_start as f32;
+        let length = self.path.length().min(MAX_SLIDER_LENGTH);
+        let tick_distance = tick_distance.max(0.0).min(length);
+        let min_distance_from_end = velocity * 10.0;
+
+        self.nested.push(NestedObject { kind: NestedKind::Head, time: start, position: self.path.position_at(0.0),
+            progress: 0.0, span: 0 });
+        for span in 0..spans {
+            let span_start = start + span as f32 * span_duration;
+            let reversed = span % 2 == 1;
+            let mut ticks = Vec::new();
+            if tick_distance > 0.0 {
+                let mut d = tick_distance;
+                while d <= length && d < length - min_distance_from_end {
+                    let progress = d / length;
+                    let time_progress = if reversed { 1.0 - progress } else { progress };
+                    ticks.push(NestedObject { kind: NestedKind::Tick, time: span_start + time_progress * span_duration,
+                        position: self.path.position_at(progress), progress: progress, span: span });
+                    d += tick_distance;
+                }
+            }
+            if reversed {
+                ticks.reverse();
+            }
+            self.nested.extend(ticks);
+            if span < spans - 1 {
+                let progress = ((span + 1) % 2) as f32;
+                self.nested.push(NestedObject { kind: NestedKind::Repeat, time: span_start + span_duration,
+                    position: self.path.position_at(progress), progress: progress, span: span });
+            }
+        }
+
+        let total = spans as f32 * span_duration;
+        let final_start = start + (spans - 1) as f32 * span_duration;
+        let last_tick = (start + total / 2.0).max(final_start + span_duration - LEGACY_LAST_TICK_OFFSET);
+        let mut progress = if span_duration > 0.0 { (last_tick - final_start) / span_duration } else { 1.0 };
+        if spans % 2 == 0 {
+            progress = 1.0 - progress;
+        }
+        self.nested.push(NestedObject { kind: NestedKind::LastTick, time: last_tick,
+            position: self.path.position_at(progress), progress: progress, span: spans - 1 });
+        let progress = (spans % 2) as f32;
+        self.nested.push(NestedObject { kind: NestedKind::Tail, time: start + total,
+            position: self.path.position_at(progress), progress: progress, span: spans - 1 });
+    }
+
+    //Shift of object caused by stacking, in osu!pixels
+    pub fn stack_offset(&self, circle_size: f32) -> (f32, f32) {
+        let offset = self.stack_height as f32 * circle_scale(circle_size) * -6.4;
+        (offset, offset)
+    }
+
+    pub fn stacked_position(&self, circle_size: f32) -> (f32, f32) {
+        let (x, y) = self.points.first().cloned().unwrap_or((0, 0));
+        let offset = self.stack_offset(circle_size);
+        (x as f32 + offset.0, y as f32 + offset.1)
+    }
+
+    pub fn get_time_start(&self) -> i32 {
+        self.time_start
+    }
+
+    pub fn time_end(&self) -> i32 {
+        self.time_start + self.length
+    }
+
+    //Combo given by the object, sliders count every nested object but tail
+    pub fn max_combo(&self) -> i32 {
+        match self.obj_type {
+            HitObjectType::Slider => self.nested.iter().filter(|n| n.kind != NestedKind::Tail).count().max(1) as i32,
+            _ => 1
+        }
+    }
+
+    //Hitsound of slider edge, 0 is head. Falls back to hitsound of the whole object
+    pub fn edge_hitsound(&self, edge: usize) -> HitSoundType {
+        match self.edge_sounds.get(edge) {
+            Some(sound) => *sound,
+            None => self.hitsound
+        }
+    }
+
+    pub fn edge_sample_sets(&self, edge: usize) -> (SampleSet, SampleSet) {
+        match self.edge_sets.get(edge) {
+            Some(sets) => *sets,
+            None => (self.hit_sample.normal_set, self.hit_sample.addition_set)
+        }
+    }
+
+    pub fn flags(&self) -> HitObjectFlags {
+        HitObjectFlags::new(self.obj_type, self.new_combo, self.colour_skip)
+    }
+
+    //Single line of [HitObjects] section
+    pub fn to_osu(&self) -> String {
+        let (x, y) = self.points.first().cloned().unwrap_or((0, 0));
+        match self.obj_type {
+            HitObjectType::Circle => {
+                format!("{},{},{},{},{},{}", x, y, self.time_start, self.flags().bits(), self.hitsound.value(),
+                    self.hit_sample.to_osu())
+            }
+            HitObjectType::Spinner => {
+                format!("{},{},{},{},{},{},{}", x, y, self.time_start, self.flags().bits(), self.hitsound.value(),
+                    self.time_start + self.length, self.hit_sample.to_osu())
+            }
+            //end time is the first field of hit sample
+            HitObjectType::Hold => {
+                format!("{},{},{},{},{},{}:{}", x, y, self.time_start, self.flags().bits(), self.hitsound.value(),
+                    self.time_start + self.length, self.hit_sample.to_osu())
+            }
+            HitObjectType::Slider => {
+                let mut curve = self.slider_type.letter().to_string();
+                for p in self.points.iter().skip(1) {
+                    curve.push_str(&format!("|{}:{}", p.0, p.1));
+                }
+                let sounds:Vec<String> = self.edge_sounds.iter().map(|s| s.value().to_string()).collect();
+                let sets:Vec<String> = self.edge_sets.iter().map(|s| format!("{}:{}", s.0.index(), s.1.index())).collect();
+                format!("{},{},{},{},{},{},{},{},{},{},{}", x, y, self.time_start, self.flags().bits(), self.hitsound.value(),
+                    curve, self.slider_repeats, self.pixel_length, sounds.join("|"), sets.join("|"), self.hit_sample.to_osu())
+            }
+        }
+    }
+}
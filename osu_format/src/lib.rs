@@ -0,0 +1,42 @@
+/*
+  osu! beatmap format: .osu and .osb parsing and writing, slider curves,
+  difficulty values, star rating and pp, .osz archives.
+  Has no graphics or audio dependencies.
+*/
+
+//Code is written as the rest of the project: try!, explicit field names and 'static,
+//manual Default of enums, max/min instead of clamp which panics on NaN bounds
+#![allow(deprecated)]
+#![allow(clippy::redundant_field_names, clippy::redundant_static_lifetimes, clippy::derivable_impls,
+         clippy::manual_clamp, clippy::match_like_matches_macro, clippy::manual_is_multiple_of)]
+
+extern crate zip;
+
+pub mod bezier;
+pub mod circle_arc;
+pub mod catmull;
+pub mod slider_path;
+pub mod stacking;
+pub mod difficulty;
+pub mod mods;
+pub mod star_rating;
+pub mod performance;
+pub mod hit_object;
+pub mod beatmap;
+pub mod error;
+pub mod event;
+pub mod easing;
+pub mod storyboard;
+pub mod osz;
+
+pub use hit_object::*;
+pub use beatmap::*;
+pub use error::{BeatmapParseError, ParseErrorKind};
+pub use event::Event;
+pub use storyboard::Storyboard;
+pub use slider_path::SliderPath;
+pub use difficulty::DifficultyAttributes;
+pub use mods::{Mods, apply_mods};
+pub use star_rating::{StarRating, star_rating};
+pub use performance::{Score, Performance, performance};
+pub use osz::{OszArchive, OszError};
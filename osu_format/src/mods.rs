@@ -0,0 +1,198 @@
+use beatmap::Beatmap;
+use difficulty::DifficultyAttributes;
+
+/*
+  Mods field is a bitmask with the same values as in osu! scores and replays.
+  Nightcore is always set together with Double Time, Perfect with Sudden Death.
+*/
+pub const MOD_NO_FAIL: i32 = 1;
+pub const MOD_EASY: i32 = 2;
+pub const MOD_HIDDEN: i32 = 8;
+pub const MOD_HARD_ROCK: i32 = 16;
+pub const MOD_SUDDEN_DEATH: i32 = 32;
+pub const MOD_DOUBLE_TIME: i32 = 64;
+pub const MOD_RELAX: i32 = 128;
+pub const MOD_HALF_TIME: i32 = 256;
+pub const MOD_NIGHTCORE: i32 = 512;
+pub const MOD_FLASHLIGHT: i32 = 1024;
+pub const MOD_AUTO: i32 = 2048;
+pub const MOD_SPUN_OUT: i32 = 4096;
+pub const MOD_AUTOPILOT: i32 = 8192;
+pub const MOD_PERFECT: i32 = 16384;
+
+//Acronyms in the order osu! shows them
+const ACRONYMS: [(&'static str, i32); 14] = [
+    ("NF", MOD_NO_FAIL), ("EZ", MOD_EASY), ("HD", MOD_HIDDEN), ("HR", MOD_HARD_ROCK),
+    ("SD", MOD_SUDDEN_DEATH), ("PF", MOD_PERFECT), ("DT", MOD_DOUBLE_TIME), ("NC", MOD_NIGHTCORE),
+    ("HT", MOD_HALF_TIME), ("FL", MOD_FLASHLIGHT), ("RX", MOD_RELAX), ("AP", MOD_AUTOPILOT),
+    ("SO", MOD_SPUN_OUT), ("AT", MOD_AUTO),
+];
+
+#[derive(PartialEq, Debug, Copy, Clone, Default)]
+pub struct Mods {
+    bits: i32
+}
+
+impl Mods {
+    pub fn from_bits(bits: i32) -> Mods {
+        let mut bits = bits;
+        if bits & MOD_NIGHTCORE > 0 {
+            bits |= MOD_DOUBLE_TIME;
+        }
+        if bits & MOD_PERFECT > 0 {
+            bits |= MOD_SUDDEN_DEATH;
+        }
+        Mods { bits: bits }
+    }
+
+    //Acronyms written together, as "HDDT". None if some of them is unknown
+    pub fn from_acronyms(text: &str) -> Option<Mods> {
+        let text = text.trim().to_uppercase();
+        //Slicing below is by bytes, acronyms are ASCII anyway
+        if !text.is_ascii() || text.len() % 2 != 0 {
+            return None;
+        }
+        let mut bits = 0;
+        for i in 0..text.len() / 2 {
+            let acronym = &text[i * 2..i * 2 + 2];
+            match ACRONYMS.iter().find(|a| a.0 == acronym) {
+                Some(a) => { bits |= a.1 }
+                None => { return None }
+            }
+        }
+        Some(Mods::from_bits(bits))
+    }
+
+    pub fn bits(&self) -> i32 {
+        self.bits
+    }
+
+    pub fn contains(&self, bits: i32) -> bool {
+        self.bits & bits == bits
+    }
+
+    //Implied mods (DT of NC, SD of PF) are not repeated
+    pub fn acronyms(&self) -> String {
+        let mut bits = self.bits;
+        if self.contains(MOD_NIGHTCORE) {
+            bits &= !MOD_DOUBLE_TIME;
+        }
+        if self.contains(MOD_PERFECT) {
+            bits &= !MOD_SUDDEN_DEATH;
+        }
+        ACRONYMS.iter().filter(|a| bits & a.1 > 0).map(|a| a.0).collect()
+    }
+
+    //Speed of the song
+    pub fn clock_rate(&self) -> f32 {
+        if self.contains(MOD_DOUBLE_TIME) {
+            1.5
+        } else if self.contains(MOD_HALF_TIME) {
+            0.75
+        } else {
+            1.0
+        }
+    }
+
+    //Difficulty settings changed by HR and EZ, clock rate is applied too
+    pub fn apply(&self, attrs: &DifficultyAttributes) -> DifficultyAttributes {
+        self.scale(attrs).with_clock_rate(self.clock_rate())
+    }
+
+    fn scale(&self, attrs: &DifficultyAttributes) -> DifficultyAttributes {
+        let mut attrs = *attrs;
+        if self.contains(MOD_HARD_ROCK) {
+            attrs.circle_size = (attrs.circle_size * 1.3).min(10.0);
+            attrs.approach_rate = (attrs.approach_rate * 1.4).min(10.0);
+            attrs.overall_difficulty = (attrs.overall_difficulty * 1.4).min(10.0);
+            attrs.hp_drain = (attrs.hp_drain * 1.4).min(10.0);
+        }
+        if self.contains(MOD_EASY) {
+            attrs.circle_size *= 0.5;
+            attrs.approach_rate *= 0.5;
+            attrs.overall_difficulty *= 0.5;
+            attrs.hp_drain *= 0.5;
+        }
+        attrs
+    }
+}
+
+/*
+  Map as it is played with mods and its gameplay values.
+  Map keeps its timing, times of attributes are in real ms at their clock rate.
+*/
+pub fn apply_mods(beatmap: &Beatmap, mods: Mods) -> (Beatmap, DifficultyAttributes) {
+    let mut map = beatmap.clone();
+    let scaled = mods.scale(&beatmap.difficulty_attributes());
+    if scaled != beatmap.difficulty_attributes() {
+        map.set_difficulty(&scaled);
+    }
+    if mods.contains(MOD_HARD_ROCK) {
+        map.flip_vertically();
+    }
+    (map, scaled.with_clock_rate(mods.clock_rate()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use beatmap::FileFormat;
+
+    #[test]
+    fn acronyms() {
+        let mods = Mods::from_acronyms("hdnc").unwrap();
+        assert!(mods.contains(MOD_HIDDEN | MOD_DOUBLE_TIME));
+        assert_eq!(mods.bits(), MOD_HIDDEN | MOD_DOUBLE_TIME | MOD_NIGHTCORE);
+        assert_eq!(mods.acronyms(), "HDNC");
+        assert_eq!(mods.clock_rate(), 1.5);
+        assert_eq!(Mods::from_bits(MOD_EASY | MOD_HALF_TIME).acronyms(), "EZHT");
+        assert!(Mods::from_acronyms("HDX").is_none());
+        assert!(Mods::from_acronyms("XX").is_none());
+        assert!(Mods::from_acronyms("€H").is_none());
+        assert!(Mods::from_acronyms("HDé").is_none());
+    }
+
+    #[test]
+    fn hard_rock_and_double_time() {
+        let mut btmp = Beatmap::new();
+        let mut lines = vec![
+            "osu file format v14",
+            "[Difficulty]",
+            "HPDrainRate:6",
+            "CircleSize:4",
+            "OverallDifficulty:8",
+            "ApproachRate:9",
+            "[TimingPoints]",
+            "0,500,4,1,0,100,1,0",
+            "[HitObjects]",
+            "100,100,1000,1,0",
+            "100,300,2000,2,0,L|200:300,1,100",
+        ];
+        btmp.from_osu(&mut lines).unwrap();
+
+        let (hr, attrs) = apply_mods(&btmp, Mods::from_acronyms("HR").unwrap());
+        assert_eq!(hr.objects()[0].points[0], (100, 284));
+        assert_eq!(*hr.objects()[1].path.points().last().unwrap(), (200.0, 84.0));
+        assert!((attrs.circle_size - 5.2).abs() < 0.001);
+        assert_eq!(attrs.approach_rate, 10.0);
+        assert!((attrs.hp_drain - 8.4).abs() < 0.001);
+        assert_eq!(hr.difficulty_attributes().approach_rate, 10.0);
+        //original map stays as it was
+        assert_eq!(btmp.objects()[0].points[0], (100, 100));
+
+        let (ez, attrs) = apply_mods(&btmp, Mods::from_acronyms("EZ").unwrap());
+        assert_eq!(ez.objects()[0].points[0], (100, 100));
+        assert_eq!(attrs.overall_difficulty, 4.0);
+
+        //AR 9 is 600 ms, 400 ms in real time with DT
+        let (dt, attrs) = apply_mods(&btmp, Mods::from_acronyms("DT").unwrap());
+        assert_eq!(dt, btmp);
+        assert_eq!(attrs.clock_rate, 1.5);
+        assert!((attrs.preempt() - 400.0).abs() < 0.01);
+        assert!((attrs.approach_rate - 31.0 / 3.0).abs() < 0.001);
+        assert!((attrs.hit_window_300() - 32.0 / 1.5).abs() < 0.001);
+
+        let (_, attrs) = apply_mods(&btmp, Mods::from_acronyms("HT").unwrap());
+        assert!((attrs.preempt() - 800.0).abs() < 0.01);
+    }
+}
@@ -0,0 +1,347 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, BufReader};
+use std::path::{Path, PathBuf};
+
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use beatmap::Beatmap;
+use error::BeatmapParseError;
+use event::Event;
+
+/*
+  .osz is a zip archive of beatmap set: .osu file for every difficulty,
+  audio, backgrounds, storyboard .osb and its sprites.
+  Names are matched case-insensitively, as osu! does on Windows.
+*/
+
+#[derive(Debug)]
+pub enum OszError {
+    Io(io::Error),
+    //not a zip archive or it is damaged
+    Zip(ZipError),
+    //.osu file of archive can't be read at all
+    Parse(String, BeatmapParseError),
+    //file is referenced by map but not packed
+    MissingFile(String),
+    //entry would be extracted outside of target folder
+    UnsafePath(String),
+}
+
+impl fmt::Display for OszError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OszError::Io(ref e) => write!(f, "{}", e),
+            OszError::Zip(ref e) => write!(f, "bad archive: {}", e),
+            OszError::Parse(ref name, ref e) => write!(f, "{}: {}", name, e),
+            OszError::MissingFile(ref name) => write!(f, "no \"{}\" in archive", name),
+            OszError::UnsafePath(ref name) => write!(f, "unsafe path \"{}\" in archive", name),
+        }
+    }
+}
+
+impl Error for OszError {
+    fn description(&self) -> &str {
+        match *self {
+            OszError::Io(_) => "io error",
+            OszError::Zip(_) => "bad archive",
+            OszError::Parse(..) => "broken beatmap",
+            OszError::MissingFile(_) => "missing file",
+            OszError::UnsafePath(_) => "unsafe path",
+        }
+    }
+}
+
+impl From<io::Error> for OszError {
+    fn from(e: io::Error) -> OszError {
+        OszError::Io(e)
+    }
+}
+
+impl From<ZipError> for OszError {
+    fn from(e: ZipError) -> OszError {
+        OszError::Zip(e)
+    }
+}
+
+pub struct OszArchive<R: Read + Seek> {
+    zip: ZipArchive<R>,
+    //entry names by index, directories are skipped
+    names: Vec<(usize, String)>,
+}
+
+impl OszArchive<File> {
+    pub fn open(path: &Path) -> Result<OszArchive<File>, OszError> {
+        OszArchive::new(try!(File::open(path)))
+    }
+}
+
+impl<R: Read + Seek> OszArchive<R> {
+    pub fn new(reader: R) -> Result<OszArchive<R>, OszError> {
+        let zip = try!(ZipArchive::new(reader));
+        let mut names = Vec::new();
+        for i in 0..zip.len() {
+            if let Some(name) = zip.name_for_index(i) {
+                if !name.ends_with('/') && !name.ends_with('\\') {
+                    names.push( (i, name.to_string()) );
+                }
+            }
+        }
+        Ok(OszArchive { zip: zip, names: names })
+    }
+
+    //Every file as named in archive
+    pub fn file_names(&self) -> Vec<&str> {
+        self.names.iter().map(|n| n.1.as_ref()).collect()
+    }
+
+    //Names of .osu files, one for every difficulty
+    pub fn difficulties(&self) -> Vec<&str> {
+        self.files_with_extension(".osu")
+    }
+
+    pub fn osb_files(&self) -> Vec<&str> {
+        self.files_with_extension(".osb")
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.index_of(name).is_some()
+    }
+
+    //Whole file, name is case-insensitive and may use either slash
+    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>, OszError> {
+        let index = match self.index_of(name) {
+            Some(i) => i,
+            None => return Err(OszError::MissingFile(name.to_string())),
+        };
+        let mut file = try!(self.zip.by_index(index));
+        let mut data = Vec::new();
+        try!(file.read_to_end(&mut data));
+        Ok(data)
+    }
+
+    /*
+      Parses difficulty with storyboard of the set added from .osb, if there is one.
+      Bad lines are skipped as in from_osu_lenient and returned as warnings,
+      broken .osb is skipped and its error is a warning too.
+    */
+    pub fn beatmap(&mut self, name: &str) -> Result<(Beatmap, Vec<BeatmapParseError>), OszError> {
+        let index = match self.index_of(name) {
+            Some(i) => i,
+            None => return Err(OszError::MissingFile(name.to_string())),
+        };
+        let mut map = Beatmap::new();
+        let mut warnings = {
+            let file = try!(self.zip.by_index(index));
+            try!(map.from_reader_lenient(BufReader::new(file)).map_err(|e| OszError::Parse(name.to_string(), e)))
+        };
+        if let Some(osb) = self.osb_for(&map) {
+            let data = try!(self.read_file(&osb));
+            let text = String::from_utf8_lossy(&data);
+            let mut lines = text.lines().collect();
+            if let Err(e) = map.load_osb(&mut lines) {
+                warnings.push(e);
+            }
+        }
+        Ok((map, warnings))
+    }
+
+    //Every difficulty in order of names, with warnings of each
+    pub fn beatmaps(&mut self) -> Result<Vec<(Beatmap, Vec<BeatmapParseError>)>, OszError> {
+        let names:Vec<String> = self.difficulties().iter().map(|n| n.to_string()).collect();
+        let mut maps = Vec::with_capacity(names.len());
+        for name in &names {
+            maps.push(try!(self.beatmap(name)));
+        }
+        Ok(maps)
+    }
+
+    pub fn audio(&mut self, map: &Beatmap) -> Result<Vec<u8>, OszError> {
+        self.read_file(map.audio_filename())
+    }
+
+    //None if map has no background set
+    pub fn background(&mut self, map: &Beatmap) -> Result<Option<Vec<u8>>, OszError> {
+        match map.background() {
+            Some(Event::Background { filename, .. }) => self.read_file(filename).map(Some),
+            _ => Ok(None)
+        }
+    }
+
+    /*
+      Unpacks every file into dir, creating folders inside it.
+      Nothing is written if some entry would end up outside of dir.
+      Returns paths of written files.
+    */
+    pub fn extract(&mut self, dir: &Path) -> Result<Vec<PathBuf>, OszError> {
+        let mut targets = Vec::with_capacity(self.names.len());
+        for &(index, ref name) in &self.names {
+            match safe_path(name) {
+                Some(path) => targets.push( (index, dir.join(path)) ),
+                None => return Err(OszError::UnsafePath(name.clone())),
+            }
+        }
+        for &(index, ref target) in &targets {
+            if let Some(parent) = target.parent() {
+                try!(fs::create_dir_all(parent));
+            }
+            let mut file = try!(self.zip.by_index(index));
+            let mut out = try!(File::create(target));
+            try!(io::copy(&mut file, &mut out));
+        }
+        Ok(targets.into_iter().map(|t| t.1).collect())
+    }
+
+    fn files_with_extension(&self, extension: &str) -> Vec<&str> {
+        self.names.iter().map(|n| n.1.as_ref()).filter(|n: &&str| n.to_lowercase().ends_with(extension)).collect()
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        let wanted = normalize_name(name);
+        self.names.iter().find(|n| normalize_name(&n.1) == wanted).map(|n| n.0)
+    }
+
+    //.osb named after the set, or the only one in archive
+    fn osb_for(&self, map: &Beatmap) -> Option<String> {
+        let osb = map.osb_filename();
+        if self.contains(&osb) {
+            return Some(osb);
+        }
+        let files = self.osb_files();
+        if files.len() == 1 { Some(files[0].to_string()) } else { None }
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    name.replace('\\', "/").to_lowercase()
+}
+
+/*
+  Relative path of archive entry, None if it is absolute, has drive letter
+  or goes up with "..". Both slashes are separators, archives made on Windows use '\'.
+*/
+pub fn safe_path(name: &str) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for part in name.split(&['/', '\\'][..]) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            _ if part.contains(':') => return None,
+            _ => path.push(part),
+        }
+    }
+    if name.starts_with('/') || name.starts_with('\\') || path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+
+    fn archive(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, text) in files {
+            writer.start_file(name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(text.as_bytes()).unwrap();
+        }
+        let mut data = writer.finish().unwrap();
+        data.set_position(0);
+        data
+    }
+
+    const MAP: &'static str = "osu file format v14\n[General]\nAudioFilename: audio.mp3\n\
+        [Metadata]\nTitle:Song\nArtist:Band\nCreator:Mapper\nVersion:Hard\n\
+        [Events]\n0,0,\"BG.jpg\",0,0\n[HitObjects]\n256,192,1000,1,0\n";
+
+    #[test]
+    fn difficulties_and_assets() {
+        let mut osz = OszArchive::new(archive(&[
+            ("Band - Song (Mapper) [Hard].osu", MAP),
+            ("audio.mp3", "mp3"),
+            ("bg.jpg", "jpg"),
+            ("Band - Song (Mapper).osb", "[Events]\nSprite,Foreground,Centre,\"sb\\star.png\",320,240\n"),
+            ("SB\\star.png", "png"),
+        ])).unwrap();
+        assert_eq!(osz.difficulties(), vec!["Band - Song (Mapper) [Hard].osu"]);
+
+        let maps = osz.beatmaps().unwrap();
+        assert_eq!(maps.len(), 1);
+        let (ref map, ref warnings) = maps[0];
+        assert!(warnings.is_empty());
+        assert_eq!(map.version(), "Hard");
+        assert_eq!(map.objects().len(), 1);
+        assert_eq!(map.osb_storyboard().sprites.len(), 1);
+        assert_eq!(osz.audio(map).unwrap(), b"mp3".to_vec());
+        //BG.jpg in map is bg.jpg in archive
+        assert_eq!(osz.background(map).unwrap(), Some(b"jpg".to_vec()));
+        assert_eq!(osz.read_file("sb/Star.png").unwrap(), b"png".to_vec());
+        match osz.read_file("missing.wav") {
+            Err(OszError::MissingFile(name)) => assert_eq!(name, "missing.wav"),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn broken_difficulty() {
+        let mut osz = OszArchive::new(archive(&[("bad.osu", "[General]\n")])).unwrap();
+        match osz.beatmaps() {
+            Err(OszError::Parse(name, _)) => assert_eq!(name, "bad.osu"),
+            other => panic!("{:?}", other),
+        }
+
+        //bad lines of good difficulty are warnings
+        let mut osz = OszArchive::new(archive(&[
+            ("a.osu", "osu file format v14\n[HitObjects]\n256,192,1000,1,0\n256,192,oops,1,0\n"),
+            ("b.osb", "[Events]\nSprite,Nowhere,Centre,\"a.png\",320,240\n"),
+        ])).unwrap();
+        let (map, warnings) = osz.beatmap("a.osu").unwrap();
+        assert_eq!(map.objects().len(), 1);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].line, 4);
+        assert!(OszArchive::new(Cursor::new(b"not a zip".to_vec())).is_err());
+    }
+
+    #[test]
+    fn unsafe_paths() {
+        assert_eq!(safe_path("sb/star.png"), Some(Path::new("sb").join("star.png")));
+        assert_eq!(safe_path("sb\\star.png"), Some(Path::new("sb").join("star.png")));
+        assert_eq!(safe_path("./a.osu"), Some(Path::new("a.osu").to_path_buf()));
+        assert_eq!(safe_path("../a.osu"), None);
+        assert_eq!(safe_path("sb\\..\\..\\a.osu"), None);
+        assert_eq!(safe_path("/etc/passwd"), None);
+        assert_eq!(safe_path("C:\\a.osu"), None);
+        assert_eq!(safe_path(""), None);
+
+        let dir = ::std::env::temp_dir().join("osu_format_unsafe_paths");
+        let mut osz = OszArchive::new(archive(&[("a.osu", MAP), ("../evil.osu", MAP)])).unwrap();
+        match osz.extract(&dir) {
+            Err(OszError::UnsafePath(name)) => assert_eq!(name, "../evil.osu"),
+            other => panic!("{:?}", other),
+        }
+        assert!(!dir.join("a.osu").exists());
+    }
+
+    #[test]
+    fn extract_files() {
+        let dir = ::std::env::temp_dir().join("osu_format_extract_files");
+        let _ = fs::remove_dir_all(&dir);
+        let mut osz = OszArchive::new(archive(&[("a.osu", MAP), ("sb\\star.png", "png")])).unwrap();
+        let written = osz.extract(&dir).unwrap();
+        assert_eq!(written, vec![dir.join("a.osu"), dir.join("sb").join("star.png")]);
+        let mut text = String::new();
+        File::open(dir.join("a.osu")).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, MAP);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
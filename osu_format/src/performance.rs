@@ -0,0 +1,372 @@
+use mods::*;
+use star_rating::{StarRating, PERFORMANCE_BASE_MULTIPLIER};
+
+/*
+  Performance points of osu!standard plays, following osu! performance calculator
+  as of osu!lazer 7342fb7f (2022-10-11), the version ported by rosu-pp 1.1.0
+  that the star rating tests are checked against.
+  Aim, speed, accuracy and Flashlight values are computed separately
+  and summed as norm with power 1.1.
+*/
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Score {
+    pub count_300: i32,
+    pub count_100: i32,
+    pub count_50: i32,
+    pub count_miss: i32,
+    pub max_combo: i32,
+    pub mods: Mods,
+}
+
+impl Score {
+    pub fn total_hits(&self) -> i32 {
+        self.count_300 + self.count_100 + self.count_50 + self.count_miss
+    }
+
+    //0..1
+    pub fn accuracy(&self) -> f32 {
+        let total = self.total_hits();
+        if total > 0 {
+            (self.count_300 * 300 + self.count_100 * 100 + self.count_50 * 50) as f32 / (total * 300) as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Performance {
+    pub total: f32,
+    pub aim: f32,
+    pub speed: f32,
+    pub accuracy: f32,
+    pub flashlight: f32,
+    //misses and guessed slider breaks
+    pub effective_miss_count: f32,
+}
+
+//Rating must be computed with mods of the score
+pub fn performance(rating: &StarRating, score: &Score) -> Performance {
+    if score.total_hits() == 0 {
+        return Performance::default();
+    }
+    let mut calc = Calculator::new(rating, score);
+    let mut multiplier = PERFORMANCE_BASE_MULTIPLIER;
+    if score.mods.contains(MOD_NO_FAIL) {
+        multiplier *= (1.0 - 0.02 * calc.effective_miss_count).max(0.9);
+    }
+    if score.mods.contains(MOD_SPUN_OUT) {
+        multiplier *= 1.0 - (rating.spinners as f64 / calc.total_hits).powf(0.85);
+    }
+
+    if score.mods.contains(MOD_RELAX) {
+        //100s and 50s are mostly misaimed notes with Relax
+        let od = rating.overall_difficulty as f64;
+        let ok_multiplier = if od > 0.0 { (1.0 - (od / 13.33).powf(1.8)).max(0.0) } else { 1.0 };
+        let meh_multiplier = if od > 0.0 { (1.0 - (od / 13.33).powf(5.0)).max(0.0) } else { 1.0 };
+        calc.effective_miss_count = (calc.effective_miss_count + score.count_100 as f64 * ok_multiplier
+            + score.count_50 as f64 * meh_multiplier).min(calc.total_hits);
+    }
+
+    let aim = calc.aim_value();
+    let speed = calc.speed_value();
+    let accuracy = calc.accuracy_value();
+    let flashlight = calc.flashlight_value();
+    let total = (aim.powf(1.1) + speed.powf(1.1) + accuracy.powf(1.1) + flashlight.powf(1.1)).powf(1.0 / 1.1) * multiplier;
+
+    Performance {
+        total: total as f32,
+        aim: aim as f32,
+        speed: speed as f32,
+        accuracy: accuracy as f32,
+        flashlight: flashlight as f32,
+        effective_miss_count: calc.effective_miss_count as f32,
+    }
+}
+
+struct Calculator<'a> {
+    rating: &'a StarRating,
+    score: &'a Score,
+    accuracy: f64,
+    total_hits: f64,
+    effective_miss_count: f64,
+}
+
+impl<'a> Calculator<'a> {
+    fn new(rating: &'a StarRating, score: &'a Score) -> Calculator<'a> {
+        let mut calc = Calculator {
+            rating: rating,
+            score: score,
+            accuracy: score.accuracy() as f64,
+            total_hits: score.total_hits() as f64,
+            effective_miss_count: 0.0,
+        };
+        calc.effective_miss_count = calc.miss_count();
+        calc
+    }
+
+    //Combo lost on slider ends is counted as misses too
+    fn miss_count(&self) -> f64 {
+        let mut combo_based = 0.0;
+        if self.rating.sliders > 0 {
+            let full_combo_threshold = self.rating.max_combo as f64 - 0.1 * self.rating.sliders as f64;
+            if (self.score.max_combo as f64) < full_combo_threshold {
+                combo_based = full_combo_threshold / (self.score.max_combo as f64).max(1.0);
+            }
+        }
+        combo_based = combo_based.min((self.score.count_100 + self.score.count_50 + self.score.count_miss) as f64);
+        combo_based.max(self.score.count_miss as f64)
+    }
+
+    fn length_bonus(&self) -> f64 {
+        let mut bonus = 0.95 + 0.4 * (self.total_hits / 2000.0).min(1.0);
+        if self.total_hits > 2000.0 {
+            bonus += (self.total_hits / 2000.0).log10() * 0.5;
+        }
+        bonus
+    }
+
+    fn combo_scaling(&self) -> f64 {
+        if self.rating.max_combo <= 0 {
+            1.0
+        } else {
+            ((self.score.max_combo as f64).powf(0.8) / (self.rating.max_combo as f64).powf(0.8)).min(1.0)
+        }
+    }
+
+    fn miss_penalty(&self, exponent: f64) -> f64 {
+        0.97 * (1.0 - (self.effective_miss_count / self.total_hits).powf(0.775)).powf(exponent)
+    }
+
+    fn aim_value(&self) -> f64 {
+        if self.score.mods.contains(MOD_AUTOPILOT) {
+            return 0.0;
+        }
+        let ar = self.rating.approach_rate as f64;
+        let mut value = difficulty_to_performance(self.rating.aim as f64);
+        let length_bonus = self.length_bonus();
+        value *= length_bonus;
+        if self.effective_miss_count > 0.0 {
+            value *= self.miss_penalty(self.effective_miss_count);
+        }
+        value *= self.combo_scaling();
+
+        let mut ar_factor = 0.0;
+        if ar > 10.33 {
+            ar_factor = 0.3 * (ar - 10.33);
+        } else if ar < 8.0 {
+            ar_factor = 0.05 * (8.0 - ar);
+        }
+        //Relax players don't have to react in time to click
+        if self.score.mods.contains(MOD_RELAX) {
+            ar_factor = 0.0;
+        }
+        value *= 1.0 + ar_factor * length_bonus;
+        if self.score.mods.contains(MOD_HIDDEN) {
+            value *= 1.0 + 0.04 * (12.0 - ar);
+        }
+
+        //dropped slider ends make sliders without them easier to aim
+        let difficult_sliders = self.rating.sliders as f64 * 0.15;
+        if self.rating.sliders > 0 {
+            let dropped = ((self.score.count_100 + self.score.count_50 + self.score.count_miss)
+                .min(self.rating.max_combo - self.score.max_combo) as f64).max(0.0).min(difficult_sliders);
+            let slider_factor = self.rating.slider_factor as f64;
+            value *= (1.0 - slider_factor) * (1.0 - dropped / difficult_sliders).powi(3) + slider_factor;
+        }
+
+        value *= self.accuracy;
+        value * (0.98 + (self.rating.overall_difficulty as f64).powi(2) / 2500.0)
+    }
+
+    fn speed_value(&self) -> f64 {
+        if self.score.mods.contains(MOD_RELAX) {
+            return 0.0;
+        }
+        let ar = self.rating.approach_rate as f64;
+        let od = self.rating.overall_difficulty as f64;
+        let mut value = difficulty_to_performance(self.rating.speed as f64);
+        let length_bonus = self.length_bonus();
+        value *= length_bonus;
+        if self.effective_miss_count > 0.0 {
+            value *= self.miss_penalty(self.effective_miss_count.powf(0.875));
+        }
+        value *= self.combo_scaling();
+
+        if ar > 10.33 {
+            value *= 1.0 + 0.3 * (ar - 10.33) * length_bonus;
+        }
+        if self.score.mods.contains(MOD_HIDDEN) {
+            value *= 1.0 + 0.04 * (12.0 - ar);
+        }
+
+        //accuracy on speed notes, assuming the worst case that other objects got all the 300s
+        let speed_notes = self.rating.speed_note_count as f64;
+        let other = self.total_hits - speed_notes;
+        let (count_300, count_100, count_50) = (self.score.count_300 as f64, self.score.count_100 as f64, self.score.count_50 as f64);
+        let relevant_300 = (count_300 - other).max(0.0);
+        let relevant_100 = (count_100 - (other - count_300).max(0.0)).max(0.0);
+        let relevant_50 = (count_50 - (other - count_300 - count_100).max(0.0)).max(0.0);
+        let relevant_accuracy = if speed_notes > 0.0 {
+            (relevant_300 * 6.0 + relevant_100 * 2.0 + relevant_50) / (speed_notes * 6.0)
+        } else {
+            0.0
+        };
+        value *= (0.95 + od * od / 750.0) * ((self.accuracy + relevant_accuracy) / 2.0).powf((14.5 - od.max(8.0)) / 2.0);
+        //50s on more than 0.2% of objects
+        let mehs = self.score.count_50 as f64 - self.total_hits / 500.0;
+        value * 0.99f64.powf(mehs.max(0.0))
+    }
+
+    fn accuracy_value(&self) -> f64 {
+        if self.score.mods.contains(MOD_RELAX) {
+            return 0.0;
+        }
+        //only circles have accuracy, sliders and spinners are assumed to be 300s
+        let circles = self.rating.circles as f64;
+        let mut better_accuracy = 0.0;
+        if circles > 0.0 {
+            better_accuracy = ((self.score.count_300 as f64 - (self.total_hits - circles)) * 6.0
+                + self.score.count_100 as f64 * 2.0 + self.score.count_50 as f64) / (circles * 6.0);
+        }
+        better_accuracy = better_accuracy.max(0.0);
+
+        let mut value = 1.52163f64.powf(self.rating.overall_difficulty as f64) * better_accuracy.powi(24) * 2.83;
+        value *= (circles / 1000.0).powf(0.3).min(1.15);
+        if self.score.mods.contains(MOD_HIDDEN) {
+            value *= 1.08;
+        }
+        if self.score.mods.contains(MOD_FLASHLIGHT) {
+            value *= 1.02;
+        }
+        value
+    }
+
+    fn flashlight_value(&self) -> f64 {
+        if !self.score.mods.contains(MOD_FLASHLIGHT) {
+            return 0.0;
+        }
+        let flashlight = self.rating.flashlight as f64;
+        let mut value = flashlight * flashlight * 25.0;
+        if self.effective_miss_count > 0.0 {
+            value *= self.miss_penalty(self.effective_miss_count.powf(0.875));
+        }
+        value *= self.combo_scaling();
+        //longer maps are harder to memorise
+        let mut length_bonus = 0.7 + 0.1 * (self.total_hits / 200.0).min(1.0);
+        if self.total_hits > 200.0 {
+            length_bonus += 0.2 * ((self.total_hits - 200.0) / 200.0).min(1.0);
+        }
+        value *= length_bonus;
+        value *= 0.5 + self.accuracy / 2.0;
+        value * (0.98 + (self.rating.overall_difficulty as f64).powi(2) / 2500.0)
+    }
+}
+
+fn difficulty_to_performance(difficulty: f64) -> f64 {
+    (5.0 * (difficulty / 0.0675).max(1.0) - 4.0).powi(3) / 100000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Read;
+    use beatmap::{Beatmap, FileFormat};
+    use star_rating::{star_rating, StarRating};
+
+    fn rating() -> StarRating {
+        StarRating {
+            stars: 5.0,
+            aim: 0.675,
+            speed: 0.0,
+            approach_rate: 9.0,
+            overall_difficulty: 5.0,
+            clock_rate: 1.0,
+            slider_factor: 1.0,
+            max_combo: 1000,
+            circles: 1000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn perfect_play() {
+        let score = Score { count_300: 1000, max_combo: 1000, ..Default::default() };
+        let pp = performance(&rating(), &score);
+        //aim: (5 * 10 - 4)^3 / 100000 * length bonus 1.15 * OD factor 0.99
+        assert!((pp.aim - 1.10817).abs() < 1e-4, "{}", pp.aim);
+        //accuracy: 1.52163^5 * 2.83
+        assert!((pp.accuracy - 23.0851).abs() < 1e-3, "{}", pp.accuracy);
+        assert!((pp.total - 27.16338).abs() < 1e-3, "{}", pp.total);
+        assert_eq!(pp.flashlight, 0.0);
+        assert_eq!(pp.effective_miss_count, 0.0);
+    }
+
+    #[test]
+    fn misses_and_mods() {
+        let perfect = performance(&rating(), &Score { count_300: 1000, max_combo: 1000, ..Default::default() });
+        let missed = performance(&rating(), &Score { count_300: 990, count_miss: 10, max_combo: 600, ..Default::default() });
+        assert_eq!(missed.effective_miss_count, 10.0);
+        assert!(missed.aim < perfect.aim);
+        assert!(missed.accuracy < perfect.accuracy);
+
+        let hidden = Score { count_300: 1000, max_combo: 1000, mods: Mods::from_bits(MOD_HIDDEN), ..Default::default() };
+        assert!(performance(&rating(), &hidden).total > perfect.total);
+        let no_fail = Score { mods: Mods::from_bits(MOD_NO_FAIL), ..missed_score() };
+        assert!(performance(&rating(), &no_fail).total < missed.total);
+        let relax = Score { count_300: 1000, max_combo: 1000, mods: Mods::from_bits(MOD_RELAX), ..Default::default() };
+        let relax = performance(&rating(), &relax);
+        assert_eq!((relax.speed, relax.accuracy), (0.0, 0.0));
+        //no AR bonus for aim with Relax: 1 + 0.3 * (11 - 10.33) * length bonus 1.15
+        let high_ar = StarRating { approach_rate: 11.0, ..rating() };
+        let nomod = performance(&high_ar, &Score { count_300: 1000, max_combo: 1000, ..Default::default() });
+        let relax = performance(&high_ar, &Score { count_300: 1000, max_combo: 1000, mods: Mods::from_bits(MOD_RELAX), ..Default::default() });
+        assert!((relax.aim * (1.0 + 0.3 * 0.67 * 1.15) - nomod.aim).abs() < 1e-4, "{} {}", relax.aim, nomod.aim);
+
+        assert_eq!(performance(&rating(), &Score::default()).total, 0.0);
+    }
+
+    fn missed_score() -> Score {
+        Score { count_300: 990, count_miss: 10, max_combo: 600, ..Default::default() }
+    }
+
+    #[test]
+    fn slider_breaks_count_as_misses() {
+        let mut rating = rating();
+        rating.sliders = 100;
+        rating.circles = 900;
+        rating.max_combo = 1200;
+        //no misses, but combo is far from full
+        let score = Score { count_300: 995, count_100: 5, max_combo: 500, ..Default::default() };
+        let pp = performance(&rating, &score);
+        assert!((pp.effective_miss_count - 1190.0 / 500.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_map_performance() {
+        let mut text = String::new();
+        File::open("tests/test.osu").unwrap().read_to_string(&mut text).unwrap();
+        let mut btmp = Beatmap::new();
+        let mut lines:Vec<&str> = text.lines().collect();
+        btmp.from_osu(&mut lines).unwrap();
+
+        //values of rosu-pp 1.1.0 for the same map and score
+        let close = |value: f32, expected: f32| (value - expected).abs() < 1e-3;
+        let score = Score { count_300: 80, count_100: 6, count_50: 2, count_miss: 1, max_combo: 200, ..Default::default() };
+        let rating = star_rating(&btmp, score.mods);
+        assert!(close(rating.speed_note_count, 33.7026), "{}", rating.speed_note_count);
+        let pp = performance(&rating, &score);
+        assert!(close(pp.speed, 0.43959), "{}", pp.speed);
+        assert!(close(pp.aim, 1.33335), "{}", pp.aim);
+        assert!(close(pp.total, 1.92411), "{}", pp.total);
+
+        let score = Score { mods: Mods::from_bits(MOD_HIDDEN | MOD_HARD_ROCK | MOD_DOUBLE_TIME), ..score };
+        let rating = star_rating(&btmp, score.mods);
+        assert!(close(rating.speed_note_count, 43.66388), "{}", rating.speed_note_count);
+        let pp = performance(&rating, &score);
+        assert!(close(pp.speed, 1.65194), "{}", pp.speed);
+        assert!(close(pp.total, 6.18547), "{}", pp.total);
+    }
+}
@@ -0,0 +1,263 @@
+use bezier::Bezier;
+use circle_arc::CircleArc;
+use catmull::Catmull;
+use hit_object::SliderType;
+
+/*
+  Flattened path of slider, same for every curve type.
+  Points are fitted to pixel length set by mapper, cumulative lengths
+  allow to find position by distance along the path instead of point index.
+  Timing is set by Beatmap after slider durations are known.
+*/
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SliderPath {
+    points: Vec<(f32, f32)>,
+    //distance from the start to every point
+    cumulative: Vec<f32>,
+    time_start: i32,
+    span_duration: f32,
+    span_count: i32,
+}
+
+impl SliderPath {
+    pub fn new(slider_type: SliderType, control_points: &[(i32, i32)], expected_length: f32) -> SliderPath {
+        let mut points = match slider_type {
+            SliderType::SliderNone => { Vec::new() }
+            SliderType::SliderPassthrough => {
+                let mut curve = CircleArc::new();
+                for p in control_points {
+                    curve.add_vertexi(*p);
+                }
+                curve.get_tesselated_points()
+            }
+            SliderType::SliderLinear => {
+                control_points.iter().map(|p| (p.0 as f32, p.1 as f32)).collect()
+            }
+            SliderType::SliderCatmul => {
+                let mut curve = Catmull::new();
+                for p in control_points {
+                    curve.add_vertexi(*p);
+                }
+                curve.get_tesselated_points()
+            }
+            SliderType::SliderBezier => {
+                let mut curve = Bezier::new();
+                for p in control_points {
+                    curve.add_vertexi(*p);
+                }
+                curve.get_tesselated_points()
+            }
+        };
+        if expected_length > 0.0 {
+            fit_to_length(&mut points, expected_length);
+        }
+        SliderPath::from_points(points)
+    }
+
+    //Path going through already flattened points
+    pub fn from_points(points: Vec<(f32, f32)>) -> SliderPath {
+        let cumulative = cumulative_lengths(&points);
+        SliderPath { points: points, cumulative: cumulative, span_count: 1, ..Default::default() }
+    }
+
+    pub fn set_timing(&mut self, time_start: i32, span_duration: f32, span_count: i32) {
+        self.time_start = time_start;
+        self.span_duration = span_duration;
+        self.span_count = span_count.max(1);
+    }
+
+    pub fn points(&self) -> &Vec<(f32, f32)> {
+        &self.points
+    }
+
+    pub fn length(&self) -> f32 {
+        match self.cumulative.last() {
+            Some(l) => *l,
+            None => 0.0
+        }
+    }
+
+    pub fn span_duration(&self) -> f32 {
+        self.span_duration
+    }
+
+    pub fn span_count(&self) -> i32 {
+        self.span_count
+    }
+
+    //Position at distance progress * length from the head, progress is clamped to 0..1
+    pub fn position_at(&self, progress: f32) -> (f32, f32) {
+        point_at_distance(&self.points, &self.cumulative, progress.max(0.0).min(1.0) * self.length())
+    }
+
+    //Unit direction of path at progress, (0, 0) for path without length
+    pub fn tangent_at(&self, progress: f32) -> (f32, f32) {
+        if self.points.len() < 2 {
+            return (0.0, 0.0);
+        }
+        let d = progress.max(0.0).min(1.0) * self.length();
+        let mut i = segment_at(&self.cumulative, d);
+        //zero length segments have no direction, take the nearest one which has
+        while i + 1 < self.points.len() && self.cumulative[i + 1] - self.cumulative[i] <= 0.0 {
+            i += 1;
+        }
+        while i > 0 && (i + 1 >= self.points.len() || self.cumulative[i + 1] - self.cumulative[i] <= 0.0) {
+            i -= 1;
+        }
+        let (a, b) = (self.points[i], self.points[i + 1]);
+        let len = self.cumulative[i + 1] - self.cumulative[i];
+        if len <= 0.0 {
+            (0.0, 0.0)
+        } else {
+            ((b.0 - a.0) / len, (b.1 - a.1) / len)
+        }
+    }
+
+    /*
+      Progress along the path at time in ms. Odd spans go back from tail to head.
+      Time before the slider gives head, after it gives the point where last span ends.
+    */
+    pub fn progress_at_time(&self, time: f32) -> f32 {
+        if self.span_duration <= 0.0 {
+            return 0.0;
+        }
+        let spans = ((time - self.time_start as f32) / self.span_duration).max(0.0).min(self.span_count as f32);
+        let span = (spans.floor() as i32).min(self.span_count - 1);
+        let k = spans - span as f32;
+        if span % 2 == 1 { 1.0 - k } else { k }
+    }
+
+    pub fn position_at_time(&self, time: f32) -> (f32, f32) {
+        self.position_at(self.progress_at_time(time))
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0) * (b.0 - a.0) + (b.1 - a.1) * (b.1 - a.1)).sqrt()
+}
+
+pub fn cumulative_lengths(points: &[(f32, f32)]) -> Vec<f32> {
+    let mut cumulative = Vec::with_capacity(points.len());
+    let mut passed = 0.0;
+    for i in 0..points.len() {
+        if i > 0 {
+            passed += distance(points[i - 1], points[i]);
+        }
+        cumulative.push(passed);
+    }
+    cumulative
+}
+
+//Index of segment (i, i + 1) containing distance d
+fn segment_at(cumulative: &[f32], d: f32) -> usize {
+    if cumulative.len() < 2 {
+        return 0;
+    }
+    let mut lo = 0;
+    let mut hi = cumulative.len() - 1;
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if cumulative[mid] <= d { lo = mid } else { hi = mid }
+    }
+    lo
+}
+
+//Point at distance d along the path, linear between flattened points
+pub fn point_at_distance(points: &[(f32, f32)], cumulative: &[f32], d: f32) -> (f32, f32) {
+    if points.is_empty() {
+        return (0.0, 0.0);
+    }
+    if points.len() == 1 || d <= 0.0 {
+        return points[0];
+    }
+    let i = segment_at(cumulative, d);
+    let len = cumulative[i + 1] - cumulative[i];
+    if len <= 0.0 {
+        return points[i + 1];
+    }
+    let k = ((d - cumulative[i]) / len).min(1.0);
+    let (a, b) = (points[i], points[i + 1]);
+    (a.0 + (b.0 - a.0) * k, a.1 + (b.1 - a.1) * k)
+}
+
+/*
+    Cuts path at length or extends its last segment,
+    so slider is exactly as long as mapper set.
+*/
+fn fit_to_length(points: &mut Vec<(f32, f32)>, length: f32) {
+    if points.len() < 2 {
+        return;
+    }
+    let mut passed = 0.0;
+    for i in 1..points.len() {
+        let (a, b) = (points[i - 1], points[i]);
+        let segment = distance(a, b);
+        if passed + segment >= length {
+            let k = if segment > 0.0 { (length - passed) / segment } else { 0.0 };
+            points.truncate(i + 1);
+            points[i] = (a.0 + (b.0 - a.0) * k, a.1 + (b.1 - a.1) * k);
+            return;
+        }
+        passed += segment;
+    }
+
+    //path is too short, last segment with non-zero length sets direction
+    let last = points.len() - 1;
+    for i in (1..points.len()).rev() {
+        let (a, b) = (points[i - 1], points[i]);
+        let segment = distance(a, b);
+        if segment > 0.0 {
+            let k = (length - passed) / segment;
+            let end = points[last];
+            points[last] = (end.0 + (b.0 - a.0) * k, end.1 + (b.1 - a.1) * k);
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: (f32, f32), b: (f32, f32)) -> bool {
+        distance(a, b) < 0.01
+    }
+
+    #[test]
+    fn position_by_distance() {
+        //uneven points, equal progress steps still give equal distances
+        let path = SliderPath::from_points(vec![(0.0, 0.0), (10.0, 0.0), (100.0, 0.0)]);
+        assert_eq!(path.length(), 100.0);
+        assert!(near(path.position_at(0.0), (0.0, 0.0)));
+        assert!(near(path.position_at(0.25), (25.0, 0.0)));
+        assert!(near(path.position_at(0.5), (50.0, 0.0)));
+        assert!(near(path.position_at(1.0), (100.0, 0.0)));
+        assert!(near(path.position_at(2.0), (100.0, 0.0)));
+        assert!(near(path.tangent_at(0.5), (1.0, 0.0)));
+    }
+
+    #[test]
+    fn fitted_to_length() {
+        let short = SliderPath::new(SliderType::SliderLinear, &[(0, 0), (0, 100)], 40.0);
+        assert!(near(*short.points().last().unwrap(), (0.0, 40.0)));
+        let long = SliderPath::new(SliderType::SliderLinear, &[(0, 0), (0, 100), (0, 100)], 150.0);
+        assert!((long.length() - 150.0).abs() < 0.01);
+        assert!(near(long.tangent_at(1.0), (0.0, 1.0)));
+    }
+
+    #[test]
+    fn repeats_go_back() {
+        let mut path = SliderPath::from_points(vec![(0.0, 0.0), (100.0, 0.0)]);
+        path.set_timing(1000, 200.0, 3);
+        assert!(near(path.position_at_time(900.0), (0.0, 0.0)));
+        assert!(near(path.position_at_time(1100.0), (50.0, 0.0)));
+        assert!(near(path.position_at_time(1200.0), (100.0, 0.0)));
+        assert!(near(path.position_at_time(1250.0), (75.0, 0.0)));
+        assert!(near(path.position_at_time(1500.0), (50.0, 0.0)));
+        //odd number of spans ends at tail
+        assert!(near(path.position_at_time(1600.0), (100.0, 0.0)));
+        assert!(near(path.position_at_time(2000.0), (100.0, 0.0)));
+        path.set_timing(1000, 200.0, 2);
+        assert!(near(path.position_at_time(1400.0), (0.0, 0.0)));
+    }
+}
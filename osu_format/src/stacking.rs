@@ -0,0 +1,226 @@
+use hit_object::{HitObject, HitObjectType};
+
+/*
+  Stacking of objects placed at the same spot, as in osu!.
+  Objects closer in time than preempt * StackLeniency and closer than
+  STACK_DISTANCE in space get stack heights, so they are drawn shifted up-left.
+  Maps older than v6 use the old algorithm, which goes forward in time.
+*/
+
+//Max distance in osu!pixels between stacked objects
+pub const STACK_DISTANCE: f32 = 3.0;
+
+struct Placement {
+    obj_type: HitObjectType,
+    position: (f32, f32),
+    end_position: (f32, f32),
+    //end of path without repeats, old algorithm stacks on it
+    path_end: (f32, f32),
+    start_time: f32,
+    end_time: f32,
+}
+
+//Sets stack_height of every object, objects must be sorted by time
+pub fn apply_stacking(objects: &mut [HitObject], preempt: f32, stack_leniency: f32, format_version: i32) {
+    let placements:Vec<Placement> = objects.iter().map(|obj| {
+        let position = match obj.points.first() {
+            Some(p) => (p.0 as f32, p.1 as f32),
+            None => (0.0, 0.0)
+        };
+        let (end_position, path_end) = if obj.obj_type == HitObjectType::Slider && !obj.path.points().is_empty() {
+            (obj.path.position_at((obj.slider_repeats.max(1) % 2) as f32), obj.path.position_at(1.0))
+        } else {
+            (position, position)
+        };
+        Placement { obj_type: obj.obj_type, position: position, end_position: end_position, path_end: path_end,
+            start_time: obj.time_start as f32, end_time: obj.time_end() as f32 }
+    }).collect();
+
+    let threshold = preempt * stack_leniency;
+    let heights = if format_version >= 6 {
+        stack_heights(&placements, threshold)
+    } else {
+        stack_heights_old(&placements, threshold)
+    };
+    for (obj, height) in objects.iter_mut().zip(heights) {
+        obj.stack_height = height;
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0) * (b.0 - a.0) + (b.1 - a.1) * (b.1 - a.1)).sqrt()
+}
+
+fn stack_heights(objects: &[Placement], threshold: f32) -> Vec<i32> {
+    let mut heights = vec![0; objects.len()];
+    if objects.is_empty() {
+        return heights;
+    }
+    let spinner = |i: usize| objects[i].obj_type == HitObjectType::Spinner;
+
+    //whole map is processed, so there is nothing to extend forward
+    let start_index = 0;
+    let end_index = objects.len() - 1;
+
+    //going back from every object, stacks are built from their last object
+    let mut extended_start_index = start_index;
+    let mut i = end_index;
+    while i > start_index {
+        let mut current = i;
+        if heights[i] != 0 || spinner(i) {
+            i -= 1;
+            continue;
+        }
+
+        if objects[i].obj_type == HitObjectType::Circle {
+            let mut n = i;
+            while n > 0 {
+                n -= 1;
+                if spinner(n) {
+                    continue;
+                }
+                if objects[current].start_time - objects[n].end_time > threshold {
+                    break;
+                }
+                if n < extended_start_index {
+                    heights[n] = 0;
+                    extended_start_index = n;
+                }
+
+                //circles stacked on slider end go down-right from it
+                if objects[n].obj_type == HitObjectType::Slider
+                    && distance(objects[n].end_position, objects[current].position) < STACK_DISTANCE {
+                    let offset = heights[current] - heights[n] + 1;
+                    for j in n + 1..i + 1 {
+                        if distance(objects[n].end_position, objects[j].position) < STACK_DISTANCE {
+                            heights[j] -= offset;
+                        }
+                    }
+                    break;
+                }
+
+                if distance(objects[n].position, objects[current].position) < STACK_DISTANCE {
+                    heights[n] = heights[current] + 1;
+                    current = n;
+                }
+            }
+        } else if objects[i].obj_type == HitObjectType::Slider {
+            let mut n = i;
+            while n > start_index {
+                n -= 1;
+                if spinner(n) {
+                    continue;
+                }
+                if objects[current].start_time - objects[n].start_time > threshold {
+                    break;
+                }
+                if distance(objects[n].end_position, objects[current].position) < STACK_DISTANCE {
+                    heights[n] = heights[current] + 1;
+                    current = n;
+                }
+            }
+        }
+        i -= 1;
+    }
+    heights
+}
+
+fn stack_heights_old(objects: &[Placement], threshold: f32) -> Vec<i32> {
+    let mut heights = vec![0; objects.len()];
+    let spinner = |i: usize| objects[i].obj_type == HitObjectType::Spinner;
+    for i in 0..objects.len() {
+        if spinner(i) || (heights[i] != 0 && objects[i].obj_type != HitObjectType::Slider) {
+            continue;
+        }
+        let mut start_time = objects[i].end_time;
+        let mut slider_stack = 0;
+        for j in i + 1..objects.len() {
+            if objects[j].start_time - threshold > start_time {
+                break;
+            }
+            if spinner(j) {
+                continue;
+            }
+            if distance(objects[j].position, objects[i].position) < STACK_DISTANCE {
+                heights[i] += 1;
+                start_time = objects[j].end_time;
+            } else if distance(objects[j].position, objects[i].path_end) < STACK_DISTANCE {
+                //objects on slider end are stacked the other way
+                slider_stack += 1;
+                heights[j] -= slider_stack;
+                start_time = objects[j].end_time;
+            }
+        }
+    }
+    heights
+}
+
+#[cfg(test)]
+mod tests {
+    use beatmap::{Beatmap, FileFormat};
+
+    fn heights(lines: &[&str]) -> Vec<i32> {
+        let mut btmp = Beatmap::new();
+        let mut lines = lines.to_vec();
+        btmp.from_osu(&mut lines).unwrap();
+        btmp.objects().iter().map(|o| o.stack_height).collect()
+    }
+
+    #[test]
+    fn stacked_circles() {
+        //AR 5 gives preempt 1200 ms, 0.7 of it is 840 ms
+        for version in &["osu file format v14", "osu file format v5"] {
+            let map = [*version, "[General]", "StackLeniency: 0.7", "[Difficulty]", "ApproachRate:5",
+                "[HitObjects]",
+                "100,100,1000,1,0", "100,100,1100,1,0", "101,101,1200,1,0",
+                "300,300,5000,1,0", "300,300,6000,1,0"];
+            assert_eq!(heights(&map), vec![2, 1, 0, 0, 0], "{}", version);
+        }
+    }
+
+    #[test]
+    fn circle_on_slider_end() {
+        for version in &["osu file format v14", "osu file format v5"] {
+            let map = [*version, "[General]", "StackLeniency: 0.7", "[Difficulty]", "ApproachRate:5",
+                "SliderMultiplier:1", "[TimingPoints]", "0,500,4,1,0,100,1,0",
+                "[HitObjects]",
+                "0,0,1000,2,0,L|100:0,1,100", "100,0,1600,1,0", "100,0,1700,1,0"];
+            assert_eq!(heights(&map), vec![0, -1, -2], "{}", version);
+        }
+    }
+
+    #[test]
+    fn old_stacking_on_repeat_slider() {
+        //slider goes back to 0:0, but v5 maps stack on end of its path
+        let map = ["osu file format v5", "[General]", "StackLeniency: 0.7", "[Difficulty]", "ApproachRate:5",
+            "SliderMultiplier:1", "[TimingPoints]", "0,500,4,1,0,100,1,0",
+            "[HitObjects]",
+            "0,0,1000,2,0,L|100:0,2,100", "100,0,2100,12,0,2150", "100,0,2200,1,0"];
+        assert_eq!(heights(&map), vec![0, 0, -1]);
+
+        //new algorithm uses end of last repeat
+        let mut map = map;
+        map[0] = "osu file format v14";
+        assert_eq!(heights(&map), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn spinners_are_skipped() {
+        let map = ["osu file format v14", "[Difficulty]", "ApproachRate:5",
+            "[HitObjects]",
+            "256,192,1000,1,0", "256,192,1100,12,0,1200", "256,192,1300,1,0"];
+        assert_eq!(heights(&map), vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn stacked_position() {
+        let mut btmp = Beatmap::new();
+        let mut lines = vec!["osu file format v14", "[HitObjects]", "100,100,1000,1,0", "100,100,1100,1,0", "100,100,1200,1,0"];
+        btmp.from_osu(&mut lines).unwrap();
+        //CS 4 scale is 0.57, every level of stack is 6.4 * 0.57 osu!pixels
+        let (x, y) = btmp.objects()[0].stacked_position(4.0);
+        assert!((x - (100.0 - 2.0 * 6.4 * 0.57)).abs() < 0.001);
+        assert_eq!(x, y);
+        assert_eq!(btmp.objects()[2].stacked_position(4.0), (100.0, 100.0));
+    }
+}
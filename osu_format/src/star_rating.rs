@@ -0,0 +1,844 @@
+use std::f64::consts::PI;
+
+use beatmap::Beatmap;
+use hit_object::{HitObject, HitObjectType, NestedKind};
+use mods::*;
+
+/*
+  Star rating of osu!standard maps, following osu! difficulty calculator
+  of osu!lazer 7342fb7f (2022-10-11), test values come from rosu-pp 1.1.0 port of it.
+  Every object after the first gets strain values from movement (aim),
+  tapping speed (speed) and reading under Flashlight. Strains decay over time,
+  their peaks in 400 ms sections are summed with decreasing weights.
+  All times here are real ms, so DT and HT make strains higher or lower.
+*/
+
+//Circles are scaled to this radius before measuring distances
+const NORMALISED_RADIUS: f64 = 50.0;
+const MIN_DELTA_TIME: f64 = 25.0;
+const MAXIMUM_SLIDER_RADIUS: f64 = NORMALISED_RADIUS * 2.4;
+const ASSUMED_SLIDER_RADIUS: f64 = NORMALISED_RADIUS * 1.8;
+
+const SECTION_LENGTH: f64 = 400.0;
+const DECAY_WEIGHT: f64 = 0.9;
+const REDUCED_STRAIN_BASELINE: f64 = 0.75;
+const DIFFICULTY_MULTIPLIER: f64 = 1.06;
+const STAR_SCALING: f64 = 0.0675;
+pub const PERFORMANCE_BASE_MULTIPLIER: f64 = 1.14;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StarRating {
+    pub stars: f32,
+    pub aim: f32,
+    pub speed: f32,
+    //computed for every map, counted in stars only with Flashlight
+    pub flashlight: f32,
+    //aim without sliders divided by aim, 1 for maps without hard sliders
+    pub slider_factor: f32,
+    //objects weighted by how close their speed strain is to the highest one
+    pub speed_note_count: f32,
+    //real time values with mods
+    pub approach_rate: f32,
+    pub overall_difficulty: f32,
+    pub hp_drain: f32,
+    pub clock_rate: f32,
+    pub max_combo: i32,
+    pub circles: i32,
+    pub sliders: i32,
+    pub spinners: i32,
+    //Peak strain of every 400 ms section, in real time
+    pub aim_strains: Vec<f32>,
+    pub speed_strains: Vec<f32>,
+    pub flashlight_strains: Vec<f32>,
+}
+
+//Hit object with stacking applied and slider path followed lazily
+struct BaseObject {
+    obj_type: HitObjectType,
+    start_time: f64,
+    position: (f64, f64),
+    end_position: (f64, f64),
+    //Slider tail is judged at its last tick, but placed at the end of slider
+    tail_position: (f64, f64),
+    lazy_end_position: (f64, f64),
+    lazy_travel_distance: f64,
+    lazy_travel_time: f64,
+    repeat_count: i32,
+}
+
+struct DifficultyObject {
+    base: usize,
+    start_time: f64,
+    delta_time: f64,
+    strain_time: f64,
+    hit_window_great: f64,
+    lazy_jump_distance: f64,
+    minimum_jump_distance: f64,
+    minimum_jump_time: f64,
+    travel_distance: f64,
+    travel_time: f64,
+    angle: Option<f64>,
+}
+
+#[derive(PartialEq)]
+enum SkillKind {
+    Aim,
+    AimNoSliders,
+    Speed,
+    Flashlight,
+}
+
+struct Skill {
+    kind: SkillKind,
+    current_strain: f64,
+    current_rhythm: f64,
+    section_peak: f64,
+    section_end: f64,
+    peaks: Vec<f64>,
+    //strain at every object, before decay to section starts
+    object_strains: Vec<f64>,
+}
+
+pub fn star_rating(beatmap: &Beatmap, mods: Mods) -> StarRating {
+    let (map, attrs) = apply_mods(beatmap, mods);
+    let clock_rate = attrs.clock_rate as f64;
+    let map_attrs = map.difficulty_attributes();
+    let radius = map_attrs.circle_radius() as f64;
+    let hidden = mods.contains(MOD_HIDDEN);
+
+    let mut rating = StarRating {
+        approach_rate: attrs.approach_rate,
+        overall_difficulty: attrs.overall_difficulty,
+        hp_drain: attrs.hp_drain,
+        clock_rate: attrs.clock_rate,
+        slider_factor: 1.0,
+        ..Default::default()
+    };
+    for obj in map.objects() {
+        match obj.obj_type {
+            //holds only come from mania maps, they are single notes here
+            HitObjectType::Circle | HitObjectType::Hold => { rating.circles += 1 }
+            HitObjectType::Spinner => { rating.spinners += 1 }
+            HitObjectType::Slider => { rating.sliders += 1 }
+        }
+        rating.max_combo += obj.max_combo();
+    }
+
+    //single object has nothing to move or tap to
+    if map.objects().len() < 2 {
+        return rating;
+    }
+
+    let bases:Vec<BaseObject> = map.objects().iter().map(|obj| base_object(obj, map_attrs.circle_size, radius)).collect();
+    let hit_window_great = 2.0 * attrs.hit_window_300() as f64;
+    let objects = difficulty_objects(&bases, clock_rate, radius, hit_window_great);
+
+    let preempt = map_attrs.preempt() as f64;
+    let visibility = Visibility {
+        radius: radius,
+        preempt: preempt,
+        //Hidden makes objects fade in slower
+        fade_in: if hidden { preempt * 0.4 } else { map_attrs.fade_in() as f64 },
+        hidden: hidden,
+    };
+    let mut skills = [Skill::new(SkillKind::Aim), Skill::new(SkillKind::AimNoSliders),
+        Skill::new(SkillKind::Speed), Skill::new(SkillKind::Flashlight)];
+    for i in 0..objects.len() {
+        for skill in skills.iter_mut() {
+            skill.process(&objects, &bases, i, &visibility);
+        }
+    }
+
+    let mut aim = skills[0].difficulty_value().sqrt() * STAR_SCALING;
+    let aim_no_sliders = skills[1].difficulty_value().sqrt() * STAR_SCALING;
+    let mut speed = skills[2].difficulty_value().sqrt() * STAR_SCALING;
+    let mut flashlight = skills[3].difficulty_value().sqrt() * STAR_SCALING;
+    if aim > 0.0 {
+        rating.slider_factor = (aim_no_sliders / aim) as f32;
+    }
+    if mods.contains(MOD_RELAX) {
+        aim *= 0.9;
+        speed = 0.0;
+        flashlight *= 0.7;
+    }
+
+    let base_aim = (5.0 * (aim / STAR_SCALING).max(1.0) - 4.0).powi(3) / 100000.0;
+    let base_speed = (5.0 * (speed / STAR_SCALING).max(1.0) - 4.0).powi(3) / 100000.0;
+    let base_flashlight = if mods.contains(MOD_FLASHLIGHT) { flashlight * flashlight * 25.0 } else { 0.0 };
+    let base = (base_aim.powf(1.1) + base_speed.powf(1.1) + base_flashlight.powf(1.1)).powf(1.0 / 1.1);
+    let stars = if base > 0.00001 {
+        PERFORMANCE_BASE_MULTIPLIER.cbrt() * 0.027 * ((100000.0 / 2.0f64.powf(1.0 / 1.1) * base).cbrt() + 4.0)
+    } else {
+        0.0
+    };
+
+    rating.stars = stars as f32;
+    rating.aim = aim as f32;
+    rating.speed = speed as f32;
+    rating.speed_note_count = skills[2].relevant_note_count() as f32;
+    rating.flashlight = flashlight as f32;
+    rating.aim_strains = skills[0].strain_peaks().iter().map(|s| *s as f32).collect();
+    rating.speed_strains = skills[2].strain_peaks().iter().map(|s| *s as f32).collect();
+    rating.flashlight_strains = skills[3].strain_peaks().iter().map(|s| *s as f32).collect();
+    rating
+}
+
+fn add(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn length(a: (f64, f64)) -> f64 {
+    (a.0 * a.0 + a.1 * a.1).sqrt()
+}
+
+fn base_object(obj: &HitObject, circle_size: f32, radius: f64) -> BaseObject {
+    let offset = obj.stack_offset(circle_size);
+    let offset = (offset.0 as f64, offset.1 as f64);
+    let stacked = |p: (f32, f32)| (p.0 as f64 + offset.0, p.1 as f64 + offset.1);
+    let position = match obj.points.first() {
+        Some(p) => stacked((p.0 as f32, p.1 as f32)),
+        None => offset
+    };
+    let mut base = BaseObject {
+        obj_type: obj.obj_type,
+        start_time: obj.time_start as f64,
+        position: position,
+        end_position: position,
+        tail_position: position,
+        lazy_end_position: position,
+        lazy_travel_distance: 0.0,
+        lazy_travel_time: 0.0,
+        repeat_count: 0,
+    };
+    if obj.obj_type != HitObjectType::Slider || obj.nested.is_empty() {
+        return base;
+    }
+
+    let spans = obj.slider_repeats.max(1);
+    let span_duration = obj.span_duration as f64;
+    let duration = span_duration * spans as f64;
+    base.repeat_count = spans - 1;
+    if let Some(tail) = obj.nested.iter().find(|n| n.kind == NestedKind::Tail) {
+        base.end_position = stacked(tail.position);
+        base.tail_position = base.end_position;
+    }
+
+    //cursor only has to stay in follow circle, and the end is judged a bit earlier
+    let tracking_end = (base.start_time + duration - 36.0).max(base.start_time + duration / 2.0);
+    base.lazy_travel_time = tracking_end - base.start_time;
+    let mut end_progress = if span_duration > 0.0 { base.lazy_travel_time / span_duration } else { 0.0 };
+    end_progress = if end_progress % 2.0 >= 1.0 { 1.0 - end_progress % 1.0 } else { end_progress % 1.0 };
+    base.lazy_end_position = stacked(obj.path.position_at(end_progress as f32));
+
+    let scaling = NORMALISED_RADIUS / radius;
+    let mut cursor = position;
+    let movements:Vec<_> = obj.nested.iter().skip(1).filter(|n| n.kind != NestedKind::Tail).collect();
+    for (i, nested) in movements.iter().enumerate() {
+        let last = i + 1 == movements.len();
+        let target = if last { base.tail_position } else { stacked(nested.position) };
+        let mut movement = sub(target, cursor);
+        let mut required = ASSUMED_SLIDER_RADIUS;
+        if last {
+            let lazy_movement = sub(base.lazy_end_position, cursor);
+            if length(lazy_movement) < length(movement) {
+                movement = lazy_movement;
+            }
+        } else if nested.kind == NestedKind::Repeat {
+            required = NORMALISED_RADIUS;
+        }
+        let movement_length = scaling * length(movement);
+        if movement_length > required {
+            let k = (movement_length - required) / movement_length;
+            cursor = add(cursor, (movement.0 * k, movement.1 * k));
+            base.lazy_travel_distance += movement_length * k;
+        }
+        if last {
+            base.lazy_end_position = cursor;
+        }
+    }
+    base
+}
+
+fn end_cursor_position(base: &BaseObject) -> (f64, f64) {
+    if base.obj_type == HitObjectType::Slider { base.lazy_end_position } else { base.position }
+}
+
+fn difficulty_objects(bases: &[BaseObject], clock_rate: f64, radius: f64, hit_window_great: f64) -> Vec<DifficultyObject> {
+    let mut scaling = NORMALISED_RADIUS / radius;
+    //small circles are harder to aim than distances alone show
+    if radius < 30.0 {
+        scaling *= 1.0 + (30.0 - radius).min(5.0) / 50.0;
+    }
+
+    let mut objects = Vec::new();
+    for i in 1..bases.len() {
+        let (current, last) = (&bases[i], &bases[i - 1]);
+        let delta_time = (current.start_time - last.start_time) / clock_rate;
+        let strain_time = delta_time.max(MIN_DELTA_TIME);
+        let mut obj = DifficultyObject {
+            base: i,
+            start_time: current.start_time / clock_rate,
+            delta_time: delta_time,
+            strain_time: strain_time,
+            hit_window_great: hit_window_great,
+            lazy_jump_distance: 0.0,
+            minimum_jump_distance: 0.0,
+            minimum_jump_time: 0.0,
+            travel_distance: 0.0,
+            travel_time: 0.0,
+            angle: None,
+        };
+        if current.obj_type == HitObjectType::Slider {
+            //repeats are harder than their distance alone shows
+            obj.travel_distance = current.lazy_travel_distance * (1.0 + current.repeat_count as f64 / 2.5).powf(1.0 / 2.5);
+            obj.travel_time = (current.lazy_travel_time / clock_rate).max(MIN_DELTA_TIME);
+        }
+
+        //nothing to aim for spinners
+        if current.obj_type != HitObjectType::Spinner && last.obj_type != HitObjectType::Spinner {
+            let last_cursor = end_cursor_position(last);
+            obj.lazy_jump_distance = length(sub(current.position, last_cursor)) * scaling;
+            obj.minimum_jump_time = strain_time;
+            obj.minimum_jump_distance = obj.lazy_jump_distance;
+            if last.obj_type == HitObjectType::Slider {
+                let last_travel_time = (last.lazy_travel_time / clock_rate).max(MIN_DELTA_TIME);
+                obj.minimum_jump_time = (strain_time - last_travel_time).max(MIN_DELTA_TIME);
+                let tail_jump_distance = length(sub(last.tail_position, current.position)) * scaling;
+                obj.minimum_jump_distance = (obj.lazy_jump_distance - (MAXIMUM_SLIDER_RADIUS - ASSUMED_SLIDER_RADIUS))
+                    .min(tail_jump_distance - MAXIMUM_SLIDER_RADIUS).max(0.0);
+            }
+            if i > 1 && bases[i - 2].obj_type != HitObjectType::Spinner {
+                let v1 = sub(end_cursor_position(&bases[i - 2]), last.position);
+                let v2 = sub(current.position, last_cursor);
+                let dot = v1.0 * v2.0 + v1.1 * v2.1;
+                let det = v1.0 * v2.1 - v1.1 * v2.0;
+                obj.angle = Some(det.atan2(dot).abs());
+            }
+        }
+        objects.push(obj);
+    }
+    objects
+}
+
+impl Skill {
+    fn new(kind: SkillKind) -> Skill {
+        Skill {
+            kind: kind,
+            current_strain: 0.0,
+            current_rhythm: 0.0,
+            section_peak: 0.0,
+            section_end: 0.0,
+            peaks: Vec::new(),
+            object_strains: Vec::new(),
+        }
+    }
+
+    fn multiplier(&self) -> f64 {
+        match self.kind {
+            SkillKind::Aim | SkillKind::AimNoSliders => 23.55,
+            SkillKind::Speed => 1375.0,
+            SkillKind::Flashlight => 0.052,
+        }
+    }
+
+    fn decay_base(&self) -> f64 {
+        match self.kind {
+            SkillKind::Speed => 0.3,
+            _ => 0.15,
+        }
+    }
+
+    fn decay(&self, ms: f64) -> f64 {
+        self.decay_base().powf(ms / 1000.0)
+    }
+
+    fn process(&mut self, objects: &[DifficultyObject], bases: &[BaseObject], index: usize, visibility: &Visibility) {
+        let current = &objects[index];
+        if index == 0 {
+            self.section_end = (current.start_time / SECTION_LENGTH).ceil() * SECTION_LENGTH;
+        }
+        while current.start_time > self.section_end {
+            self.peaks.push(self.section_peak);
+            //strain at the section start decays from the previous object
+            let previous = objects[index - 1].start_time;
+            self.section_peak = self.current_strain * self.current_rhythm_or_one() * self.decay(self.section_end - previous);
+            self.section_end += SECTION_LENGTH;
+        }
+
+        let strain = match self.kind {
+            SkillKind::Aim | SkillKind::AimNoSliders => {
+                self.current_strain *= self.decay(current.delta_time);
+                self.current_strain += aim_strain(objects, bases, index, self.kind == SkillKind::Aim) * self.multiplier();
+                self.current_strain
+            }
+            SkillKind::Speed => {
+                self.current_strain *= self.decay(current.strain_time);
+                self.current_strain += speed_strain(objects, bases, index) * self.multiplier();
+                self.current_rhythm = rhythm_bonus(objects, bases, index);
+                self.current_strain * self.current_rhythm
+            }
+            SkillKind::Flashlight => {
+                self.current_strain *= self.decay(current.delta_time);
+                self.current_strain += flashlight_strain(objects, bases, index, visibility)
+                    * self.multiplier();
+                self.current_strain
+            }
+        };
+        self.section_peak = self.section_peak.max(strain);
+        self.object_strains.push(strain);
+    }
+
+    //Count of objects with strain near the top one, each of them counts from 0 to 1
+    fn relevant_note_count(&self) -> f64 {
+        let max_strain = self.object_strains.iter().fold(0.0f64, |m, s| m.max(*s));
+        if max_strain <= 0.0 {
+            return 0.0;
+        }
+        self.object_strains.iter().map(|s| 1.0 / (1.0 + (6.0 - s / max_strain * 12.0).exp())).sum()
+    }
+
+    fn current_rhythm_or_one(&self) -> f64 {
+        if self.kind == SkillKind::Speed { self.current_rhythm } else { 1.0 }
+    }
+
+    fn strain_peaks(&self) -> Vec<f64> {
+        let mut peaks = self.peaks.clone();
+        peaks.push(self.section_peak);
+        peaks
+    }
+
+    fn difficulty_value(&self) -> f64 {
+        let peaks = self.strain_peaks();
+        match self.kind {
+            SkillKind::Flashlight => peaks.iter().sum::<f64>() * DIFFICULTY_MULTIPLIER,
+            SkillKind::Speed => weighted_peaks(peaks, 5, 1.04),
+            _ => weighted_peaks(peaks, 10, DIFFICULTY_MULTIPLIER),
+        }
+    }
+}
+
+/*
+  Highest peaks are reduced first to soften short spikes,
+  then sorted peaks are summed with weights 1, 0.9, 0.81 ...
+*/
+fn weighted_peaks(peaks: Vec<f64>, reduced_count: usize, multiplier: f64) -> f64 {
+    let mut strains:Vec<f64> = peaks.into_iter().filter(|p| *p > 0.0).collect();
+    strains.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    for i in 0..strains.len().min(reduced_count) {
+        let scale = (1.0 + 9.0 * (i as f64 / reduced_count as f64)).log10();
+        strains[i] *= REDUCED_STRAIN_BASELINE + (1.0 - REDUCED_STRAIN_BASELINE) * scale;
+    }
+    strains.sort_by(|a, b| b.partial_cmp(a).unwrap());
+
+    let mut difficulty = 0.0;
+    let mut weight = 1.0;
+    for strain in strains {
+        difficulty += strain * weight;
+        weight *= DECAY_WEIGHT;
+    }
+    difficulty * multiplier
+}
+
+fn wide_angle_bonus(angle: f64) -> f64 {
+    (0.75 * ((5.0 / 6.0 * PI).min((PI / 6.0).max(angle)) - PI / 6.0)).sin().powi(2)
+}
+
+fn acute_angle_bonus(angle: f64) -> f64 {
+    1.0 - wide_angle_bonus(angle)
+}
+
+const WIDE_ANGLE_MULTIPLIER: f64 = 1.5;
+const ACUTE_ANGLE_MULTIPLIER: f64 = 1.95;
+const SLIDER_MULTIPLIER: f64 = 1.35;
+const VELOCITY_CHANGE_MULTIPLIER: f64 = 0.75;
+
+fn aim_strain(objects: &[DifficultyObject], bases: &[BaseObject], index: usize, with_sliders: bool) -> f64 {
+    let current = &objects[index];
+    if index <= 1 || bases[current.base].obj_type == HitObjectType::Spinner
+        || bases[objects[index - 1].base].obj_type == HitObjectType::Spinner {
+        return 0.0;
+    }
+    let last = &objects[index - 1];
+    let last_last = &objects[index - 2];
+    let last_is_slider = bases[last.base].obj_type == HitObjectType::Slider;
+    let last_last_is_slider = bases[last_last.base].obj_type == HitObjectType::Slider;
+
+    //velocity of jump, or of slider end and jump after it if that is faster
+    let mut curr_velocity = current.lazy_jump_distance / current.strain_time;
+    if last_is_slider && with_sliders {
+        let travel_velocity = last.travel_distance / last.travel_time;
+        let movement_velocity = current.minimum_jump_distance / current.minimum_jump_time;
+        curr_velocity = curr_velocity.max(movement_velocity + travel_velocity);
+    }
+    let mut prev_velocity = last.lazy_jump_distance / last.strain_time;
+    if last_last_is_slider && with_sliders {
+        let travel_velocity = last_last.travel_distance / last_last.travel_time;
+        let movement_velocity = last.minimum_jump_distance / last.minimum_jump_time;
+        prev_velocity = prev_velocity.max(movement_velocity + travel_velocity);
+    }
+
+    let mut wide_bonus = 0.0;
+    let mut acute_bonus = 0.0;
+    let mut slider_bonus = 0.0;
+    let mut velocity_change_bonus = 0.0;
+    let mut strain = curr_velocity;
+
+    //angles matter only for the same rhythm
+    if current.strain_time.max(last.strain_time) < 1.25 * current.strain_time.min(last.strain_time) {
+        if let (Some(curr_angle), Some(last_angle), Some(last_last_angle)) = (current.angle, last.angle, last_last.angle) {
+            let angle_bonus = curr_velocity.min(prev_velocity);
+            wide_bonus = wide_angle_bonus(curr_angle);
+            acute_bonus = acute_angle_bonus(curr_angle);
+            if current.strain_time > 100.0 {
+                acute_bonus = 0.0;
+            } else {
+                acute_bonus *= acute_angle_bonus(last_angle)
+                    * angle_bonus.min(125.0 / current.strain_time)
+                    * (PI / 2.0 * ((100.0 - current.strain_time) / 25.0).min(1.0)).sin().powi(2)
+                    * (PI / 2.0 * (current.lazy_jump_distance.max(50.0).min(100.0) - 50.0) / 50.0).sin().powi(2);
+            }
+            //repeated angles are easier
+            wide_bonus *= angle_bonus * (1.0 - wide_bonus.min(wide_angle_bonus(last_angle).powi(3)));
+            acute_bonus *= 0.5 + 0.5 * (1.0 - acute_bonus.min(acute_angle_bonus(last_last_angle).powi(3)));
+        }
+    }
+
+    if prev_velocity.max(curr_velocity) != 0.0 {
+        //slider ends are followed by cursor, so their travel counts as movement
+        let prev_velocity = (last.lazy_jump_distance + last_last.travel_distance) / last.strain_time;
+        let curr_velocity = (current.lazy_jump_distance + last.travel_distance) / current.strain_time;
+        let change = (prev_velocity - curr_velocity).abs();
+        let dist_ratio = (PI / 2.0 * change / prev_velocity.max(curr_velocity)).sin().powi(2);
+        let overlap_buff = (125.0 / current.strain_time.min(last.strain_time)).min(change);
+        velocity_change_bonus = overlap_buff * dist_ratio;
+        velocity_change_bonus *= (current.strain_time.min(last.strain_time) / current.strain_time.max(last.strain_time)).powi(2);
+    }
+
+    if last_is_slider {
+        slider_bonus = last.travel_distance / last.travel_time;
+    }
+
+    strain += (acute_bonus * ACUTE_ANGLE_MULTIPLIER)
+        .max(wide_bonus * WIDE_ANGLE_MULTIPLIER + velocity_change_bonus * VELOCITY_CHANGE_MULTIPLIER);
+    if with_sliders {
+        strain += slider_bonus * SLIDER_MULTIPLIER;
+    }
+    strain
+}
+
+const SINGLE_SPACING_THRESHOLD: f64 = 125.0;
+const MIN_SPEED_BONUS: f64 = 75.0;
+const SPEED_BALANCING_FACTOR: f64 = 40.0;
+
+fn speed_strain(objects: &[DifficultyObject], bases: &[BaseObject], index: usize) -> f64 {
+    let current = &objects[index];
+    if bases[current.base].obj_type == HitObjectType::Spinner {
+        return 0.0;
+    }
+    let mut strain_time = current.strain_time;
+
+    //doubles which can be tapped with one press are easier
+    let mut doubletapness = 1.0;
+    if let Some(next) = objects.get(index + 1) {
+        let curr_delta = current.delta_time.max(1.0);
+        let next_delta = next.delta_time.max(1.0);
+        let delta_difference = (next_delta - curr_delta).abs();
+        let speed_ratio = curr_delta / curr_delta.max(delta_difference);
+        let window_ratio = (curr_delta / current.hit_window_great).min(1.0).powi(2);
+        doubletapness = speed_ratio.powf(1.0 - window_ratio);
+    }
+
+    //streams faster than 300 hit window are not harder to tap
+    strain_time /= (strain_time / current.hit_window_great / 0.93).max(0.92).min(1.0);
+
+    let mut speed_bonus = 1.0;
+    if strain_time < MIN_SPEED_BONUS {
+        speed_bonus = 1.0 + 0.75 * ((MIN_SPEED_BONUS - strain_time) / SPEED_BALANCING_FACTOR).powi(2);
+    }
+    let travel_distance = if index > 0 { objects[index - 1].travel_distance } else { 0.0 };
+    let distance = SINGLE_SPACING_THRESHOLD.min(travel_distance + current.minimum_jump_distance);
+    (speed_bonus + speed_bonus * (distance / SINGLE_SPACING_THRESHOLD).powf(3.5)) * doubletapness / strain_time
+}
+
+const HISTORY_TIME_MAX: f64 = 5000.0;
+const RHYTHM_MULTIPLIER: f64 = 0.75;
+
+//Multiplier of speed strain for changing rhythms, 1 for constant ones
+fn rhythm_bonus(objects: &[DifficultyObject], bases: &[BaseObject], index: usize) -> f64 {
+    let current = &objects[index];
+    if bases[current.base].obj_type == HitObjectType::Spinner {
+        return 0.0;
+    }
+    let previous = |i: usize| &objects[index - 1 - i];
+    let mut previous_island_size = 0;
+    let mut rhythm_complexity_sum = 0.0;
+    let mut island_size = 1;
+    let mut start_ratio = 0.0;
+    let mut first_delta_switch = false;
+
+    let historical_count = index.min(32);
+    let mut rhythm_start = 0;
+    while rhythm_start + 2 < historical_count && current.start_time - previous(rhythm_start).start_time < HISTORY_TIME_MAX {
+        rhythm_start += 1;
+    }
+
+    for i in (1..rhythm_start + 1).rev() {
+        let curr = previous(i - 1);
+        let prev = previous(i);
+        let last = previous(i + 1);
+        let mut decay = (HISTORY_TIME_MAX - (current.start_time - curr.start_time)) / HISTORY_TIME_MAX;
+        decay = decay.min((historical_count - i) as f64 / historical_count as f64);
+
+        let (curr_delta, prev_delta, last_delta) = (curr.strain_time, prev.strain_time, last.strain_time);
+        let curr_ratio = 1.0 + 6.0 * (PI / (prev_delta.min(curr_delta) / prev_delta.max(curr_delta))).sin().powi(2).min(0.5);
+        let window_penalty = (((prev_delta - curr_delta).abs() - curr.hit_window_great * 0.3).max(0.0)
+            / (curr.hit_window_great * 0.3)).min(1.0);
+        let mut effective_ratio = window_penalty * curr_ratio;
+
+        if first_delta_switch {
+            if !(prev_delta > 1.25 * curr_delta || prev_delta * 1.25 < curr_delta) {
+                //island of same rhythm goes on
+                if island_size < 7 {
+                    island_size += 1;
+                }
+            } else {
+                if bases[curr.base].obj_type == HitObjectType::Slider {
+                    effective_ratio *= 0.125;
+                }
+                if bases[prev.base].obj_type == HitObjectType::Slider {
+                    effective_ratio *= 0.25;
+                }
+                if previous_island_size == island_size {
+                    effective_ratio *= 0.25;
+                }
+                if previous_island_size % 2 == island_size % 2 {
+                    effective_ratio *= 0.5;
+                }
+                if last_delta > prev_delta + 10.0 && prev_delta > curr_delta + 10.0 {
+                    effective_ratio *= 0.125;
+                }
+                rhythm_complexity_sum += (effective_ratio * start_ratio).sqrt() * decay
+                    * (4.0 + island_size as f64).sqrt() / 2.0 * (4.0 + previous_island_size as f64).sqrt() / 2.0;
+                start_ratio = effective_ratio;
+                previous_island_size = island_size;
+                if prev_delta * 1.25 < curr_delta {
+                    first_delta_switch = false;
+                }
+                island_size = 1;
+            }
+        } else if prev_delta > 1.25 * curr_delta {
+            //rhythm speeds up, new island starts
+            first_delta_switch = true;
+            start_ratio = effective_ratio;
+            island_size = 1;
+        }
+    }
+    (4.0 + rhythm_complexity_sum * RHYTHM_MULTIPLIER).sqrt() / 2.0
+}
+
+const MAX_OPACITY_BONUS: f64 = 0.4;
+const HIDDEN_BONUS: f64 = 0.2;
+const MIN_VELOCITY: f64 = 0.5;
+const FLASHLIGHT_SLIDER_MULTIPLIER: f64 = 1.3;
+const MIN_ANGLE_MULTIPLIER: f64 = 0.2;
+
+//How objects are seen, used by flashlight
+struct Visibility {
+    radius: f64,
+    preempt: f64,
+    fade_in: f64,
+    hidden: bool,
+}
+
+//Opacity of object at time in ms of map, Hidden fades it out before its time
+fn opacity_at(base: &BaseObject, time: f64, visibility: &Visibility) -> f64 {
+    if time > base.start_time {
+        return 0.0;
+    }
+    let fade_in_start = base.start_time - visibility.preempt;
+    let opacity = ((time - fade_in_start) / visibility.fade_in).max(0.0).min(1.0);
+    if visibility.hidden {
+        let fade_out_start = base.start_time - visibility.preempt + visibility.fade_in;
+        let fade_out_duration = visibility.preempt * 0.3;
+        opacity.min(1.0 - ((time - fade_out_start) / fade_out_duration).max(0.0).min(1.0))
+    } else {
+        opacity
+    }
+}
+
+fn flashlight_strain(objects: &[DifficultyObject], bases: &[BaseObject], index: usize, visibility: &Visibility) -> f64 {
+    let current = &objects[index];
+    let current_base = &bases[current.base];
+    if current_base.obj_type == HitObjectType::Spinner {
+        return 0.0;
+    }
+    let scaling = 52.0 / visibility.radius;
+    let mut small_dist_nerf = 1.0;
+    let mut cumulative_strain_time = 0.0;
+    let mut result = 0.0;
+    let mut last = current;
+    let mut angle_repeat_count = 0.0;
+
+    //objects still visible in memory, going back in time
+    for i in 0..index.min(10) {
+        let obj = &objects[index - 1 - i];
+        let base = &bases[obj.base];
+        if base.obj_type != HitObjectType::Spinner {
+            let jump_distance = length(sub(current_base.position, base.end_position));
+            cumulative_strain_time += last.strain_time;
+            //objects within flashlight circle are easy to see
+            if i == 0 {
+                small_dist_nerf = (jump_distance / 75.0).min(1.0);
+            }
+            //only first object of stack counts
+            let stack_nerf = (obj.lazy_jump_distance / scaling / 25.0).min(1.0);
+            let opacity_bonus = 1.0 + MAX_OPACITY_BONUS * (1.0 - opacity_at(current_base, base.start_time, visibility));
+            result += stack_nerf * opacity_bonus * scaling * jump_distance / cumulative_strain_time;
+
+            if let (Some(a), Some(b)) = (obj.angle, current.angle) {
+                if (a - b).abs() < 0.02 {
+                    angle_repeat_count += (1.0 - 0.1 * i as f64).max(0.0);
+                }
+            }
+        }
+        last = obj;
+    }
+    result = (small_dist_nerf * result).powi(2);
+    if visibility.hidden {
+        result *= 1.0 + HIDDEN_BONUS;
+    }
+    result *= MIN_ANGLE_MULTIPLIER + (1.0 - MIN_ANGLE_MULTIPLIER) / (angle_repeat_count + 1.0);
+
+    if current_base.obj_type == HitObjectType::Slider {
+        //long and fast sliders need more memorisation
+        let pixel_travel_distance = current_base.lazy_travel_distance / scaling;
+        let mut slider_bonus = (pixel_travel_distance / current.travel_time - MIN_VELOCITY).max(0.0).sqrt();
+        slider_bonus *= pixel_travel_distance;
+        if current_base.repeat_count > 0 {
+            slider_bonus /= (current_base.repeat_count + 1) as f64;
+        }
+        result += slider_bonus * FLASHLIGHT_SLIDER_MULTIPLIER;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::weighted_peaks;
+    use std::fs::File;
+    use std::io::Read;
+    use beatmap::FileFormat;
+
+    fn map(objects: &[String]) -> Beatmap {
+        let mut btmp = Beatmap::new();
+        let mut lines = vec!["osu file format v14", "[Difficulty]", "CircleSize:4", "OverallDifficulty:8",
+            "ApproachRate:9", "[TimingPoints]", "0,300,4,1,0,100,1,0", "[HitObjects]"];
+        for o in objects {
+            lines.push(o);
+        }
+        btmp.from_osu(&mut lines).unwrap();
+        btmp
+    }
+
+    //Objects every interval ms, going back and forth distance apart, shifted so they don't stack
+    fn jumps(count: i32, interval: i32, distance: i32) -> Beatmap {
+        let objects:Vec<String> = (0..count).map(|i| {
+            format!("{},{},{},1,0", 256 - distance / 2 + (i % 2) * distance, 192 + (i / 2 % 2) * 10, i * interval)
+        }).collect();
+        map(&objects)
+    }
+
+    #[test]
+    fn peak_weighting() {
+        //single section is reduced to 0.75 of its value
+        assert!((weighted_peaks(vec![100.0], 10, 1.0) - 75.0).abs() < 1e-9);
+        //second one is reduced by log10(1.9) part of baseline
+        let second = 50.0 * (0.75 + 0.25 * 1.9f64.log10());
+        assert!((weighted_peaks(vec![0.0, 100.0, 50.0], 10, 1.0) - (75.0 + 0.9 * second)).abs() < 1e-9);
+        assert_eq!(weighted_peaks(vec![], 10, 1.0), 0.0);
+    }
+
+    #[test]
+    fn trivial_maps() {
+        let empty = map(&[]);
+        assert_eq!(star_rating(&empty, Mods::default()).stars, 0.0);
+        let single = map(&["256,192,1000,1,0".to_string()]);
+        let rating = star_rating(&single, Mods::default());
+        assert_eq!(rating.stars, 0.0);
+        assert_eq!(rating.max_combo, 1);
+        assert_eq!(rating.circles, 1);
+    }
+
+    #[test]
+    fn harder_patterns_rate_higher() {
+        let slow = star_rating(&jumps(64, 225, 200), Mods::default());
+        let fast = star_rating(&jumps(64, 150, 200), Mods::default());
+        let far = star_rating(&jumps(64, 150, 300), Mods::default());
+        assert!(slow.stars > 0.0);
+        assert!(fast.stars > slow.stars);
+        assert!(far.aim > fast.aim);
+        assert!((far.speed - fast.speed).abs() < 0.01 * fast.speed);
+
+        //aim is the same as with 1.5 times shorter intervals
+        let dt = star_rating(&jumps(64, 225, 200), Mods::from_bits(MOD_DOUBLE_TIME));
+        assert!((dt.aim - fast.aim).abs() < 0.001);
+        assert!(dt.stars > slow.stars);
+        let ht = star_rating(&jumps(64, 225, 200), Mods::from_bits(MOD_HALF_TIME));
+        assert!(ht.stars < slow.stars);
+
+        //flashlight counts only with its mod
+        let fl = star_rating(&jumps(64, 225, 200), Mods::from_bits(MOD_FLASHLIGHT));
+        assert!(slow.flashlight > 0.0);
+        assert_eq!(fl.flashlight, slow.flashlight);
+        assert!(fl.stars > slow.stars);
+        let hdfl = star_rating(&jumps(64, 225, 200), Mods::from_bits(MOD_FLASHLIGHT | MOD_HIDDEN));
+        assert!(hdfl.flashlight > fl.flashlight);
+    }
+
+    #[test]
+    fn strain_peaks_per_section() {
+        //sections end every 400 ms from 400 ms, last object at 18900 ms is in the 48th one
+        let rating = star_rating(&jumps(64, 300, 200), Mods::default());
+        assert_eq!(rating.aim_strains.len(), 48);
+        assert_eq!(rating.aim_strains.len(), rating.speed_strains.len());
+        assert!(rating.aim_strains.iter().all(|s| *s >= 0.0));
+    }
+
+    #[test]
+    fn test_map_rating() {
+        let mut text = String::new();
+        File::open("tests/test.osu").unwrap().read_to_string(&mut text).unwrap();
+        let mut btmp = Beatmap::new();
+        let mut lines:Vec<&str> = text.lines().collect();
+        btmp.from_osu(&mut lines).unwrap();
+
+        let rating = star_rating(&btmp, Mods::default());
+        assert_eq!((rating.circles, rating.sliders, rating.spinners), (27, 59, 3));
+        assert_eq!(rating.max_combo, 313);
+        //values of rosu-pp 1.1.0 for the same map and mods
+        let close = |value: f32, expected: f32| (value - expected).abs() < 1e-3;
+        assert!(close(rating.stars, 1.66867), "{}", rating.stars);
+        assert!(close(rating.aim, 0.85879), "{}", rating.aim);
+        assert!(close(rating.speed, 0.72471), "{}", rating.speed);
+        assert!(close(rating.flashlight, 0.24835), "{}", rating.flashlight);
+        assert!(close(rating.slider_factor, 0.91001), "{}", rating.slider_factor);
+        //result doesn't change between runs
+        assert_eq!(star_rating(&btmp, Mods::default()), rating);
+        let hr = star_rating(&btmp, Mods::from_bits(MOD_HARD_ROCK));
+        assert!(hr.stars > rating.stars);
+        let hrdt = star_rating(&btmp, Mods::from_bits(MOD_HARD_ROCK | MOD_DOUBLE_TIME));
+        assert!(close(hrdt.stars, 2.36250), "{}", hrdt.stars);
+        assert!(close(hrdt.aim, 1.23545), "{}", hrdt.aim);
+        assert!(close(hrdt.speed, 0.99536), "{}", hrdt.speed);
+        let hdfl = star_rating(&btmp, Mods::from_bits(MOD_HIDDEN | MOD_FLASHLIGHT));
+        assert!(close(hdfl.flashlight, 0.28865), "{}", hdfl.flashlight);
+        assert!(close(hdfl.stars, 1.91831), "{}", hdfl.stars);
+        let relax = star_rating(&btmp, Mods::from_bits(MOD_RELAX));
+        assert!(close(relax.stars, 1.33027), "{}", relax.stars);
+    }
+}
@@ -0,0 +1,862 @@
+use easing::Easing;
+use error::*;
+use event::split_quoted;
+
+/*
+    Storyboard objects from [Events] section of .osu or from .osb file.
+    Only parsing and evaluation is there: state_at returns what renderer should draw,
+    so all of it works without GPU.
+*/
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Layer {
+    Background,
+    Fail,
+    Pass,
+    Foreground,
+    Overlay,
+}
+
+impl Default for Layer {
+    fn default() -> Layer { Layer::Background }
+}
+
+const LAYERS: [(Layer, &'static str); 5] = [
+    (Layer::Background, "Background"),
+    (Layer::Fail, "Fail"),
+    (Layer::Pass, "Pass"),
+    (Layer::Foreground, "Foreground"),
+    (Layer::Overlay, "Overlay"),
+];
+
+impl Layer {
+    pub fn from_osu(value: &str) -> Option<Layer> {
+        LAYERS.iter().enumerate()
+            .find(|&(i, l)| l.1 == value || i.to_string() == value)
+            .map(|(_, l)| l.0)
+    }
+
+    pub fn name(&self) -> &'static str {
+        LAYERS.iter().find(|l| l.0 == *self).unwrap().1
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Origin {
+    TopLeft,
+    Centre,
+    CentreLeft,
+    TopRight,
+    BottomCentre,
+    TopCentre,
+    Custom,
+    CentreRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Default for Origin {
+    fn default() -> Origin { Origin::TopLeft }
+}
+
+const ORIGINS: [(Origin, &'static str); 10] = [
+    (Origin::TopLeft, "TopLeft"),
+    (Origin::Centre, "Centre"),
+    (Origin::CentreLeft, "CentreLeft"),
+    (Origin::TopRight, "TopRight"),
+    (Origin::BottomCentre, "BottomCentre"),
+    (Origin::TopCentre, "TopCentre"),
+    (Origin::Custom, "Custom"),
+    (Origin::CentreRight, "CentreRight"),
+    (Origin::BottomLeft, "BottomLeft"),
+    (Origin::BottomRight, "BottomRight"),
+];
+
+impl Origin {
+    pub fn from_osu(value: &str) -> Option<Origin> {
+        ORIGINS.iter().enumerate()
+            .find(|&(i, o)| o.1 == value || i.to_string() == value)
+            .map(|(_, o)| o.0)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ORIGINS.iter().find(|o| o.0 == *self).unwrap().1
+    }
+
+    //Anchor inside of the image, (0, 0) is top left and (1, 1) is bottom right
+    pub fn anchor(&self) -> (f32, f32) {
+        match *self {
+            Origin::TopLeft | Origin::Custom => (0.0, 0.0),
+            Origin::Centre => (0.5, 0.5),
+            Origin::CentreLeft => (0.0, 0.5),
+            Origin::TopRight => (1.0, 0.0),
+            Origin::BottomCentre => (0.5, 1.0),
+            Origin::TopCentre => (0.5, 0.0),
+            Origin::CentreRight => (1.0, 0.5),
+            Origin::BottomLeft => (0.0, 1.0),
+            Origin::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LoopType {
+    LoopForever,
+    LoopOnce,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Animation {
+    pub frame_count: i32,
+    pub frame_delay: f32,
+    pub loop_type: LoopType,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CommandEvent {
+    Fade,
+    Move,
+    MoveX,
+    MoveY,
+    Scale,
+    VectorScale,
+    Rotate,
+    Colour,
+    FlipH,
+    FlipV,
+    Additive,
+}
+
+impl CommandEvent {
+    //Count of values in one set of parameters
+    fn arity(&self) -> usize {
+        match *self {
+            CommandEvent::Move | CommandEvent::VectorScale => 2,
+            CommandEvent::Colour => 3,
+            CommandEvent::FlipH | CommandEvent::FlipV | CommandEvent::Additive => 0,
+            _ => 1,
+        }
+    }
+
+    fn letter(&self) -> &'static str {
+        match *self {
+            CommandEvent::Fade => "F",
+            CommandEvent::Move => "M",
+            CommandEvent::MoveX => "MX",
+            CommandEvent::MoveY => "MY",
+            CommandEvent::Scale => "S",
+            CommandEvent::VectorScale => "V",
+            CommandEvent::Rotate => "R",
+            CommandEvent::Colour => "C",
+            CommandEvent::FlipH | CommandEvent::FlipV | CommandEvent::Additive => "P",
+        }
+    }
+}
+
+/*
+    Single transformation. Times are relative to loop or trigger start
+    when command belongs to one of them.
+    Shorthand lines with several sets of values are split into several commands.
+*/
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Command {
+    pub event: CommandEvent,
+    pub easing: Easing,
+    pub start_time: i32,
+    pub end_time: i32,
+    pub start_value: [f32; 3],
+    pub end_value: [f32; 3],
+}
+
+impl Command {
+    fn shifted(&self, offset: i32) -> Command {
+        Command { start_time: self.start_time + offset, end_time: self.end_time + offset, ..*self }
+    }
+
+    fn value_at(&self, time: f32, component: usize) -> f32 {
+        let from = self.start_value[component];
+        let to = self.end_value[component];
+        if time >= self.end_time as f32 {
+            to
+        } else if time <= self.start_time as f32 {
+            from
+        } else {
+            let progress = (time - self.start_time as f32) / (self.end_time - self.start_time) as f32;
+            from + (to - from) * self.easing.apply(progress)
+        }
+    }
+
+    //Parameter commands with equal start and end last until the end of sprite
+    fn is_active(&self, time: f32) -> bool {
+        time >= self.start_time as f32 && (time <= self.end_time as f32 || self.start_time == self.end_time)
+    }
+
+    pub fn to_osu(&self) -> String {
+        let mut line = format!("{},{},{},{}", self.event.letter(), self.easing.index(), self.start_time, self.end_time);
+        match self.event {
+            CommandEvent::FlipH => line.push_str(",H"),
+            CommandEvent::FlipV => line.push_str(",V"),
+            CommandEvent::Additive => line.push_str(",A"),
+            _ => {
+                let arity = self.event.arity();
+                for i in 0..arity {
+                    line.push_str(&format!(",{}", self.start_value[i]));
+                }
+                for i in 0..arity {
+                    line.push_str(&format!(",{}", self.end_value[i]));
+                }
+            }
+        }
+        line
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub start_time: i32,
+    pub loop_count: i32,
+    pub commands: Vec<Command>,
+}
+
+impl Loop {
+    //Length of single iteration
+    pub fn duration(&self) -> i32 {
+        self.commands.iter().map(|c| c.end_time).max().unwrap_or(0)
+    }
+
+    fn expand(&self, out: &mut Vec<Command>) {
+        let duration = self.duration();
+        for i in 0..self.loop_count.max(1) {
+            for cmd in &self.commands {
+                out.push(cmd.shifted(self.start_time + i * duration));
+            }
+        }
+    }
+}
+
+/*
+    Commands which run when event (HitSoundClap, Passing, Failing...) happens
+    between start_time and end_time.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub name: String,
+    pub start_time: i32,
+    pub end_time: i32,
+    pub group: i32,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub layer: Layer,
+    pub origin: Origin,
+    pub filepath: String,
+    pub x: f32,
+    pub y: f32,
+    //Some for Animation objects
+    pub animation: Option<Animation>,
+    pub commands: Vec<Command>,
+    pub loops: Vec<Loop>,
+    pub triggers: Vec<Trigger>,
+}
+
+//Evaluated sprite at some moment
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SpriteState {
+    pub visible: bool,
+    pub position: (f32, f32),
+    pub scale: (f32, f32),
+    //radians
+    pub rotation: f32,
+    //RGB, 0 to 1
+    pub colour: (f32, f32, f32),
+    pub opacity: f32,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub additive: bool,
+    //Frame of Animation, 0 for Sprite
+    pub frame: i32,
+}
+
+impl Sprite {
+    //All commands with loops unrolled and fired triggers added, in absolute time
+    fn timeline(&self, fired: &[(usize, f32)]) -> Vec<Command> {
+        let mut timeline = self.commands.clone();
+        for l in &self.loops {
+            l.expand(&mut timeline);
+        }
+        for &(index, time) in fired {
+            if let Some(trigger) = self.triggers.get(index) {
+                if time >= trigger.start_time as f32 && time <= trigger.end_time as f32 {
+                    for cmd in &trigger.commands {
+                        timeline.push(cmd.shifted(time.round() as i32));
+                    }
+                }
+            }
+        }
+        timeline
+    }
+
+    pub fn start_time(&self) -> i32 {
+        self.timeline(&[]).iter().map(|c| c.start_time).min().unwrap_or(0)
+    }
+
+    pub fn end_time(&self) -> i32 {
+        self.timeline(&[]).iter().map(|c| c.end_time).max().unwrap_or(0)
+    }
+
+    pub fn state_at(&self, time: f32) -> SpriteState {
+        self.state_with_triggers(time, &[])
+    }
+
+    //fired is list of (index in triggers, time when trigger event happened)
+    pub fn state_with_triggers(&self, time: f32, fired: &[(usize, f32)]) -> SpriteState {
+        let timeline = self.timeline(fired);
+        let start = timeline.iter().map(|c| c.start_time).min().unwrap_or(0);
+        let end = timeline.iter().map(|c| c.end_time).max().unwrap_or(0);
+
+        let is = |e: CommandEvent| move |c: &&Command| c.event == e;
+        let scale = value_at(timeline.iter().filter(is(CommandEvent::Scale)), time, 0, 1.0);
+        let opacity = value_at(timeline.iter().filter(is(CommandEvent::Fade)), time, 0, 1.0);
+        let frame = match self.animation {
+            Some(ref anim) if anim.frame_count > 0 && anim.frame_delay > 0.0 && time > start as f32 => {
+                let frame = ((time - start as f32) / anim.frame_delay) as i32;
+                match anim.loop_type {
+                    LoopType::LoopForever => frame % anim.frame_count,
+                    LoopType::LoopOnce => frame.min(anim.frame_count - 1),
+                }
+            }
+            _ => 0
+        };
+
+        SpriteState {
+            visible: !timeline.is_empty() && time >= start as f32 && time <= end as f32 && opacity > 0.0,
+            position: (
+                position_at(&timeline, time, 0, self.x),
+                position_at(&timeline, time, 1, self.y),
+            ),
+            scale: (
+                scale * value_at(timeline.iter().filter(is(CommandEvent::VectorScale)), time, 0, 1.0),
+                scale * value_at(timeline.iter().filter(is(CommandEvent::VectorScale)), time, 1, 1.0),
+            ),
+            rotation: value_at(timeline.iter().filter(is(CommandEvent::Rotate)), time, 0, 0.0),
+            colour: (
+                value_at(timeline.iter().filter(is(CommandEvent::Colour)), time, 0, 255.0) / 255.0,
+                value_at(timeline.iter().filter(is(CommandEvent::Colour)), time, 1, 255.0) / 255.0,
+                value_at(timeline.iter().filter(is(CommandEvent::Colour)), time, 2, 255.0) / 255.0,
+            ),
+            opacity: opacity,
+            flip_h: timeline.iter().any(|c| c.event == CommandEvent::FlipH && c.is_active(time)),
+            flip_v: timeline.iter().any(|c| c.event == CommandEvent::FlipV && c.is_active(time)),
+            additive: timeline.iter().any(|c| c.event == CommandEvent::Additive && c.is_active(time)),
+            frame: frame,
+        }
+    }
+
+    pub fn to_osu(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        match self.animation {
+            Some(ref anim) => out.push(format!("Animation,{},{},\"{}\",{},{},{},{},{}",
+                self.layer.name(), self.origin.name(), self.filepath, self.x, self.y,
+                anim.frame_count, anim.frame_delay,
+                match anim.loop_type { LoopType::LoopForever => "LoopForever", LoopType::LoopOnce => "LoopOnce" })),
+            None => out.push(format!("Sprite,{},{},\"{}\",{},{}",
+                self.layer.name(), self.origin.name(), self.filepath, self.x, self.y)),
+        }
+        for cmd in &self.commands {
+            out.push(format!(" {}", cmd.to_osu()));
+        }
+        for l in &self.loops {
+            out.push(format!(" L,{},{}", l.start_time, l.loop_count));
+            for cmd in &l.commands {
+                out.push(format!("  {}", cmd.to_osu()));
+            }
+        }
+        for t in &self.triggers {
+            if t.group != 0 {
+                out.push(format!(" T,{},{},{},{}", t.name, t.start_time, t.end_time, t.group));
+            } else {
+                out.push(format!(" T,{},{},{}", t.name, t.start_time, t.end_time));
+            }
+            for cmd in &t.commands {
+                out.push(format!("  {}", cmd.to_osu()));
+            }
+        }
+        out
+    }
+}
+
+/*
+    Value of one component at time.
+    Before first command its start value is used, after command ends its end value stays.
+*/
+//Command that sets value at given time, and whether it is already running or only gives the start value
+fn active_command<'a, I: Iterator<Item=&'a Command>>(commands: I, time: f32) -> Option<(&'a Command, bool)> {
+    let mut first: Option<&Command> = None;
+    let mut current: Option<&Command> = None;
+    for cmd in commands {
+        let earlier = match first { Some(f) => cmd.start_time < f.start_time, None => true };
+        if earlier {
+            first = Some(cmd);
+        }
+        let later = match current { Some(c) => cmd.start_time >= c.start_time, None => true };
+        if cmd.start_time as f32 <= time && later {
+            current = Some(cmd);
+        }
+    }
+    match (current, first) {
+        (Some(cmd), _) => Some((cmd, true)),
+        (None, Some(cmd)) => Some((cmd, false)),
+        (None, None) => None,
+    }
+}
+
+fn value_at<'a, I: Iterator<Item=&'a Command>>(commands: I, time: f32, component: usize, default: f32) -> f32 {
+    match active_command(commands, time) {
+        Some((cmd, true)) => cmd.value_at(time, component),
+        Some((cmd, false)) => cmd.start_value[component],
+        None => default,
+    }
+}
+
+//M and MX/MY share one timeline per axis, M keeps the axis in its own component
+fn position_at(timeline: &[Command], time: f32, axis: usize, default: f32) -> f32 {
+    let single = if axis == 0 { CommandEvent::MoveX } else { CommandEvent::MoveY };
+    let commands = timeline.iter().filter(|c| c.event == CommandEvent::Move || c.event == single);
+    match active_command(commands, time) {
+        Some((cmd, running)) => {
+            let component = if cmd.event == CommandEvent::Move { axis } else { 0 };
+            if running { cmd.value_at(time, component) } else { cmd.start_value[component] }
+        }
+        None => default,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Storyboard {
+    pub sprites: Vec<Sprite>,
+    //$name and value from [Variables] section of .osb
+    variables: Vec<(String, String)>,
+    //last opened loop or trigger, nested commands go there
+    compound: Option<CommandTarget>,
+}
+
+//Parser state is not compared
+impl PartialEq for Storyboard {
+    fn eq(&self, other: &Storyboard) -> bool {
+        self.sprites == other.sprites
+    }
+}
+
+//Where new commands go to
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum CommandTarget {
+    Sprite,
+    Loop,
+    Trigger,
+}
+
+impl Storyboard {
+    pub fn new() -> Storyboard {
+        Storyboard { ..Default::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn layer(&self, layer: Layer) -> Vec<&Sprite> {
+        self.sprites.iter().filter(|s| s.layer == layer).collect()
+    }
+
+    /*
+        Parses single line of [Events] section. Leading spaces or underscores
+        mark commands of previous sprite, so line must not be trimmed from the left.
+        Returns false if line is not a storyboard line.
+    */
+    pub fn parse_line(&mut self, raw: &str) -> Result<bool, BeatmapParseError> {
+        let line = self.substitute(raw.trim_right());
+        let depth = line.chars().take_while(|c| *c == ' ' || *c == '_').count();
+        let tokens = split_quoted(&line[depth..]);
+        if depth == 0 {
+            return match tokens[0].as_ref() {
+                "Sprite" | "4" => {
+                    let sprite = try!(parse_sprite(&tokens, &line, false));
+                    self.sprites.push(sprite);
+                    self.compound = None;
+                    Ok(true)
+                }
+                "Animation" | "6" => {
+                    let sprite = try!(parse_sprite(&tokens, &line, true));
+                    self.sprites.push(sprite);
+                    self.compound = None;
+                    Ok(true)
+                }
+                _ => Ok(false)
+            };
+        }
+
+        let sprite = match self.sprites.last_mut() {
+            Some(s) => s,
+            None => return Err(BeatmapParseError::new(ParseErrorKind::InvalidValue, "command", &line)),
+        };
+        let target = if depth > 1 {
+            match self.compound {
+                Some(target) => target,
+                None => return Err(BeatmapParseError::new(ParseErrorKind::InvalidValue, "command", &line)),
+            }
+        } else {
+            CommandTarget::Sprite
+        };
+
+        match tokens[0].as_ref() {
+            "L" => {
+                let start_time = try!(parse_field("startTime", try!(token_str(&tokens, 1, "startTime", &line))));
+                let loop_count = try!(parse_field("loopCount", try!(token_str(&tokens, 2, "loopCount", &line))));
+                sprite.loops.push(Loop { start_time: start_time, loop_count: loop_count, commands: Vec::new() });
+                self.compound = Some(CommandTarget::Loop);
+            }
+            "T" => {
+                let name = try!(token_str(&tokens, 1, "triggerName", &line)).to_string();
+                let start_time = try!(parse_field("startTime", try!(token_str(&tokens, 2, "startTime", &line))));
+                let end_time = try!(parse_field("endTime", try!(token_str(&tokens, 3, "endTime", &line))));
+                let group = if tokens.len() > 4 { try!(parse_field("groupNumber", &tokens[4])) } else { 0 };
+                sprite.triggers.push(Trigger { name: name, start_time: start_time, end_time: end_time, group: group, commands: Vec::new() });
+                self.compound = Some(CommandTarget::Trigger);
+            }
+            _ => {
+                let commands = try!(parse_command(&tokens, &line));
+                let list = match target {
+                    CommandTarget::Sprite => &mut sprite.commands,
+                    CommandTarget::Loop => &mut sprite.loops.last_mut().unwrap().commands,
+                    CommandTarget::Trigger => &mut sprite.triggers.last_mut().unwrap().commands,
+                };
+                list.extend(commands);
+            }
+        }
+        Ok(true)
+    }
+
+    /*
+        Reads .osb file. Sprites from .osb are drawn below the ones from .osu,
+        so they are placed before already parsed sprites.
+    */
+    pub fn from_osb(&mut self, lines: &mut Vec<&str>) -> Result<(), BeatmapParseError> {
+        let mut osb = Storyboard::new();
+        let mut section = None;
+        for (n, raw) in lines.iter().enumerate() {
+            let trimmed = raw.trim_left_matches('\u{feff}').trim();
+            if trimmed.is_empty() || trimmed.starts_with("//") {
+                continue;
+            }
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                section = Some(trimmed.to_string());
+                continue;
+            }
+            match section.as_ref().map(|s| s.as_ref()) {
+                Some("[Variables]") => {
+                    let eq = match trimmed.find('=') {
+                        Some(eq) => eq,
+                        None => return Err(BeatmapParseError::new(ParseErrorKind::MissingField, "value", trimmed).at(None, n + 1)),
+                    };
+                    osb.variables.push( (trimmed[..eq].to_string(), trimmed[eq + 1..].to_string()) );
+                    //longer names first, so $ab is not replaced by value of $a
+                    osb.variables.sort_by_key(|v| ::std::cmp::Reverse(v.0.len()));
+                }
+                Some("[Events]") => {
+                    try!(osb.parse_line(raw).map_err(|e| e.at(None, n + 1)));
+                }
+                _ => {}
+            }
+        }
+        osb.sprites.append(&mut self.sprites);
+        self.sprites = osb.sprites;
+        Ok(())
+    }
+
+    //.osb file contents
+    pub fn to_osb(&self) -> String {
+        let mut out = vec!["[Events]".to_string()];
+        out.extend(self.to_osu());
+        out.push(String::new());
+        out.join("\r\n")
+    }
+
+    /*
+        Lines for [Events] section. Sprites keep their order, so reading them back
+        gives the same storyboard. Layer comments are written as stable does when
+        sprites are grouped by layers, and repeated where a sprite goes back to lower layer.
+    */
+    pub fn to_osu(&self) -> Vec<String> {
+        let header = |i: usize| format!("//Storyboard Layer {} ({})", i, LAYERS[i].1);
+        let mut out = Vec::new();
+        let mut next = 0;
+        for sprite in &self.sprites {
+            let layer = sprite.layer as usize;
+            if layer < next {
+                out.push(header(layer));
+            }
+            while next <= layer {
+                out.push(header(next));
+                next += 1;
+            }
+            out.extend(sprite.to_osu());
+        }
+        while next < LAYERS.len() {
+            out.push(header(next));
+            next += 1;
+        }
+        out
+    }
+
+    fn substitute(&self, line: &str) -> String {
+        let mut line = line.to_string();
+        if line.contains('$') {
+            for (name, value) in &self.variables {
+                line = line.replace(name.as_str(), value);
+            }
+        }
+        line
+    }
+}
+
+fn token_str<'a>(tokens: &'a [String], index: usize, field: &'static str, line: &str) -> Result<&'a str, BeatmapParseError> {
+    match tokens.get(index) {
+        Some(t) => Ok(t.as_ref()),
+        None => Err(BeatmapParseError::new(ParseErrorKind::MissingField, field, line)),
+    }
+}
+
+fn parse_sprite(tokens: &[String], line: &str, animated: bool) -> Result<Sprite, BeatmapParseError> {
+    let layer_name = try!(token_str(tokens, 1, "layer", line));
+    let layer = try!(Layer::from_osu(layer_name).ok_or(BeatmapParseError::new(ParseErrorKind::InvalidValue, "layer", layer_name)));
+    let origin_name = try!(token_str(tokens, 2, "origin", line));
+    let origin = try!(Origin::from_osu(origin_name).ok_or(BeatmapParseError::new(ParseErrorKind::InvalidValue, "origin", origin_name)));
+    let filepath = try!(token_str(tokens, 3, "filepath", line)).to_string();
+    let x = try!(parse_field("x", try!(token_str(tokens, 4, "x", line))));
+    let y = try!(parse_field("y", try!(token_str(tokens, 5, "y", line))));
+    let animation = if animated {
+        let loop_type = match tokens.get(8).map(|t| t.as_ref()) {
+            Some("LoopOnce") | Some("1") => LoopType::LoopOnce,
+            _ => LoopType::LoopForever,
+        };
+        Some(Animation {
+            frame_count: try!(parse_field("frameCount", try!(token_str(tokens, 6, "frameCount", line)))),
+            frame_delay: try!(parse_field("frameDelay", try!(token_str(tokens, 7, "frameDelay", line)))),
+            loop_type: loop_type,
+        })
+    } else {
+        None
+    };
+    Ok(Sprite {
+        layer: layer,
+        origin: origin,
+        filepath: filepath,
+        x: x,
+        y: y,
+        animation: animation,
+        commands: Vec::new(),
+        loops: Vec::new(),
+        triggers: Vec::new(),
+    })
+}
+
+//event,easing,starttime,endtime,params... Empty endtime means endtime = starttime
+fn parse_command(tokens: &[String], line: &str) -> Result<Vec<Command>, BeatmapParseError> {
+    let event = match tokens[0].as_ref() {
+        "F" => CommandEvent::Fade,
+        "M" => CommandEvent::Move,
+        "MX" => CommandEvent::MoveX,
+        "MY" => CommandEvent::MoveY,
+        "S" => CommandEvent::Scale,
+        "V" => CommandEvent::VectorScale,
+        "R" => CommandEvent::Rotate,
+        "C" => CommandEvent::Colour,
+        "P" => match try!(token_str(tokens, 4, "parameter", line)) {
+            "H" => CommandEvent::FlipH,
+            "V" => CommandEvent::FlipV,
+            "A" => CommandEvent::Additive,
+            other => return Err(BeatmapParseError::new(ParseErrorKind::InvalidValue, "parameter", other)),
+        },
+        other => return Err(BeatmapParseError::new(ParseErrorKind::InvalidValue, "event", other)),
+    };
+    let easing_index = try!(parse_field("easing", try!(token_str(tokens, 1, "easing", line))));
+    let easing = try!(Easing::from_index(easing_index)
+        .ok_or(BeatmapParseError::new(ParseErrorKind::InvalidValue, "easing", &tokens[1])));
+    let start_time:i32 = try!(parse_field("startTime", try!(token_str(tokens, 2, "startTime", line))));
+    let end_token = try!(token_str(tokens, 3, "endTime", line));
+    let end_time:i32 = if end_token.is_empty() { start_time } else { try!(parse_field("endTime", end_token)) };
+
+    let arity = event.arity();
+    if arity == 0 {
+        return Ok(vec![Command {
+            event: event, easing: easing, start_time: start_time, end_time: end_time,
+            start_value: [0.0; 3], end_value: [0.0; 3],
+        }]);
+    }
+
+    let mut values:Vec<f32> = Vec::new();
+    for t in &tokens[4..] {
+        values.push(try!(parse_field("params", t)));
+    }
+    if values.len() < arity || values.len() % arity != 0 {
+        return Err(BeatmapParseError::new(ParseErrorKind::MissingField, "params", line));
+    }
+
+    let mut commands = Vec::new();
+    let sets = values.len() / arity;
+    if sets == 1 {
+        let mut value = [0.0; 3];
+        value[..arity].clone_from_slice(&values[..arity]);
+        commands.push(Command {
+            event: event, easing: easing, start_time: start_time, end_time: end_time,
+            start_value: value, end_value: value,
+        });
+    } else {
+        //shorthand: every next pair of sets lasts the same time as the first one
+        let duration = end_time - start_time;
+        for i in 0..sets - 1 {
+            let mut from = [0.0; 3];
+            let mut to = [0.0; 3];
+            from[..arity].clone_from_slice(&values[i * arity..(i + 1) * arity]);
+            to[..arity].clone_from_slice(&values[(i + 1) * arity..(i + 2) * arity]);
+            commands.push(Command {
+                event: event, easing: easing,
+                start_time: start_time + duration * i as i32,
+                end_time: end_time + duration * i as i32,
+                start_value: from, end_value: to,
+            });
+        }
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storyboard(lines: &[&str]) -> Storyboard {
+        let mut sb = Storyboard::new();
+        for line in lines {
+            assert!(sb.parse_line(line).unwrap());
+        }
+        sb
+    }
+
+    #[test]
+    fn sprite_commands() {
+        let sb = storyboard(&[
+            "Sprite,Foreground,Centre,\"sb/star.png\",320,240",
+            " F,0,1000,2000,0,1",
+            " M,0,1000,2000,0,0,100,200",
+            " S,4,1000,2000,1,2",
+            " C,0,1000,,255,0,0",
+            " P,0,1500,1600,H",
+        ]);
+        let sprite = &sb.sprites[0];
+        assert_eq!(sprite.layer, Layer::Foreground);
+        assert_eq!(sprite.origin, Origin::Centre);
+
+        let before = sprite.state_at(500.0);
+        assert!(!before.visible);
+        assert_eq!(before.position, (0.0, 0.0));
+        assert_eq!(before.opacity, 0.0);
+
+        let mid = sprite.state_at(1500.0);
+        assert!(mid.visible);
+        assert_eq!(mid.position, (50.0, 100.0));
+        assert_eq!(mid.opacity, 0.5);
+        assert_eq!(mid.scale, (1.75, 1.75));
+        assert_eq!(mid.colour, (1.0, 0.0, 0.0));
+        assert!(mid.flip_h);
+
+        let after = sprite.state_at(2500.0);
+        assert!(!after.visible);
+        assert_eq!(after.position, (100.0, 200.0));
+        assert!(!after.flip_h);
+    }
+
+    #[test]
+    fn move_then_move_y() {
+        let sb = storyboard(&[
+            "Sprite,Foreground,Centre,\"sb/star.png\",320,240",
+            " M,0,0,1000,0,0,100,100",
+            " MY,0,2000,3000,300,400",
+        ]);
+        let sprite = &sb.sprites[0];
+        assert_eq!(sprite.state_at(500.0).position, (50.0, 50.0));
+        assert_eq!(sprite.state_at(1500.0).position, (100.0, 100.0));
+        assert_eq!(sprite.state_at(2500.0).position, (100.0, 350.0));
+        assert_eq!(sprite.state_at(3500.0).position, (100.0, 400.0));
+    }
+
+    #[test]
+    fn shorthand_and_loops() {
+        let sb = storyboard(&[
+            "Animation,Background,TopLeft,\"sb/a.png\",0,0,4,100,LoopOnce",
+            " F,0,0,100,0,1,0",
+            " L,1000,3",
+            "  MX,0,0,500,0,100",
+        ]);
+        let sprite = &sb.sprites[0];
+        assert_eq!(sprite.commands.len(), 2);
+        assert_eq!(sprite.state_at(150.0).opacity, 0.5);
+        assert_eq!(sprite.end_time(), 2500);
+        assert_eq!(sprite.state_at(1750.0).position.0, 50.0);
+        assert_eq!(sprite.state_at(150.0).frame, 1);
+        assert_eq!(sprite.state_at(2000.0).frame, 3);
+    }
+
+    #[test]
+    fn triggers() {
+        let sb = storyboard(&[
+            "Sprite,Pass,Centre,\"sb/flash.png\",320,240",
+            " F,0,0,,0",
+            " T,HitSoundClap,0,10000",
+            "  F,0,0,200,1,0",
+        ]);
+        let sprite = &sb.sprites[0];
+        assert_eq!(sprite.triggers[0].commands.len(), 1);
+        assert_eq!(sprite.state_at(5100.0).opacity, 0.0);
+        assert_eq!(sprite.state_with_triggers(5100.0, &[(0, 5000.0)]).opacity, 0.5);
+    }
+
+    #[test]
+    fn alternating_layers_round_trip() {
+        let sb = storyboard(&[
+            "Sprite,Foreground,Centre,\"a.png\",0,0",
+            " F,0,0,1000,1",
+            "Sprite,Background,Centre,\"b.png\",0,0",
+            " F,0,0,1000,1",
+            "Sprite,Foreground,Centre,\"c.png\",0,0",
+            " F,0,0,1000,1",
+            "Sprite,Pass,Centre,\"d.png\",0,0",
+        ]);
+        let written = sb.to_osu();
+        assert_eq!(written.last().unwrap(), "//Storyboard Layer 4 (Overlay)");
+        let reread = storyboard(&written.iter().filter(|l| !l.starts_with("//")).map(|l| l.as_str()).collect::<Vec<_>>());
+        assert_eq!(reread, sb);
+    }
+
+    #[test]
+    fn osb_variables() {
+        let mut sb = Storyboard::new();
+        let mut lines = vec![
+            "[Variables]",
+            "$pos=320,240",
+            "$p=0,0",
+            "[Events]",
+            "Sprite,Background,Centre,\"bg.jpg\",$pos",
+            " M,0,0,1000,$p,$pos",
+        ];
+        sb.from_osb(&mut lines).unwrap();
+        assert_eq!(sb.sprites[0].x, 320.0);
+        assert_eq!(sb.sprites[0].state_at(500.0).position, (160.0, 120.0));
+
+        let written = sb.to_osb();
+        let mut reread = Storyboard::new();
+        reread.from_osb(&mut written.lines().collect()).unwrap();
+        assert_eq!(reread.sprites, sb.sprites);
+    }
+}
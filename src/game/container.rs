@@ -3,10 +3,14 @@ use std::path::Path;
 
 use game::config::*;
 use game::stated_game_app::*;
+use game::play_result::*;
 
 pub struct Container {
     pub config: Config,
-    pub app: GameApp
+    pub app: GameApp,
+    //Local leaderboard of current map, best first
+    pub results: Vec<PlayResult>,
+    pub last_result: Option<PlayResult>,
 }
 
 impl Container {
@@ -20,12 +24,28 @@ impl Container {
         }
 
         let mut app = GameApp::new();
+        //Every target needs its own row, paths are kept in GameState order for set_state's binary search
         app.add_state(GameState::Loading, vec![GameState::Initial].as_ref());
         app.add_state(GameState::Initial, vec![GameState::Settings, GameState::SongChoose].as_ref());
+        app.add_state(GameState::Settings, vec![GameState::Initial].as_ref());
+        app.add_state(GameState::SongChoose, vec![GameState::Initial, GameState::SongPlaying].as_ref());
+        app.add_state(GameState::SongPlaying, vec![GameState::Pause, GameState::PlayResult, GameState::PlayFail].as_ref());
+        app.add_state(GameState::PlayResult, vec![GameState::SongChoose, GameState::SongPlaying].as_ref());
+        app.add_state(GameState::Pause, vec![GameState::SongChoose, GameState::SongPlaying].as_ref());
+        app.add_state(GameState::PlayFail, vec![GameState::SongChoose, GameState::SongPlaying].as_ref());
 
         Container {
             config: cfg,
-            app: app.clone()
+            app: app.clone(),
+            results: Vec::new(),
+            last_result: None,
         }
     }
+
+    //Finished play goes to leaderboard and is shown on result screen
+    pub fn add_result(&mut self, result: PlayResult) {
+        insert_result(&mut self.results, result.clone());
+        self.last_result = Some(result);
+        self.app.set_state(GameState::PlayResult);
+    }
 }
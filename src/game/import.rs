@@ -0,0 +1,29 @@
+use std::path::{Path, PathBuf};
+
+use osu_format::osz::{OszArchive, OszError, safe_path};
+use osu_format::error::BeatmapParseError;
+use game::config::Config;
+
+/*
+  Unpacks .osz into own folder of beatmap_dir named as the archive,
+  "123 Artist - Title.osz" goes to "Songs/123 Artist - Title".
+  Every difficulty is parsed first, so sets with unreadable difficulties are not imported,
+  bad lines are only warnings and returned with name of their difficulty.
+*/
+pub fn import_osz(config: &Config, osz: &Path) -> Result<(PathBuf, Vec<(String, BeatmapParseError)>), OszError> {
+    let name = osz.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let folder = match safe_path(name) {
+        Some(folder) => folder,
+        None => return Err(OszError::UnsafePath(name.to_string())),
+    };
+    let mut archive = try!(OszArchive::open(osz));
+    let names:Vec<String> = archive.difficulties().iter().map(|n| n.to_string()).collect();
+    let mut warnings = Vec::new();
+    for name in names {
+        let (_, errors) = try!(archive.beatmap(&name));
+        warnings.extend(errors.into_iter().map(|e| (name.clone(), e)));
+    }
+    let dir = Path::new(&config.general.beatmap_dir).join(folder);
+    try!(archive.extract(&dir));
+    Ok((dir, warnings))
+}
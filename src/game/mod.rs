@@ -1,3 +1,5 @@
 pub mod config;
 pub mod stated_game_app;
 pub mod container;
+pub mod play_result;
+pub mod import;
@@ -0,0 +1,36 @@
+use osu_format::beatmap::Beatmap;
+use osu_format::star_rating::star_rating;
+use osu_format::performance::{Score, Performance, performance};
+
+//Finished play as shown on result screen and local leaderboard
+#[derive(Debug, Clone)]
+pub struct PlayResult {
+    pub total_score: i32,
+    pub stats: Score,
+    pub pp: Performance,
+}
+
+impl PlayResult {
+    pub fn new(beatmap: &Beatmap, total_score: i32, stats: Score) -> PlayResult {
+        let rating = star_rating(beatmap, stats.mods);
+        PlayResult {
+            total_score: total_score,
+            stats: stats,
+            pp: performance(&rating, &stats),
+        }
+    }
+
+    //Score with pp next to it, as "1234567 +HDDT 98.50% 512x 123pp"
+    pub fn summary(&self) -> String {
+        let mods = self.stats.mods.acronyms();
+        format!("{}{} {:.2}% {}x {:.0}pp", self.total_score,
+            if mods.is_empty() { String::new() } else { format!(" +{}", mods) },
+            self.stats.accuracy() * 100.0, self.stats.max_combo, self.pp.total)
+    }
+}
+
+//Best plays first, by score as in osu!
+pub fn insert_result(leaderboard: &mut Vec<PlayResult>, result: PlayResult) {
+    let index = leaderboard.iter().position(|r| r.total_score < result.total_score).unwrap_or(leaderboard.len());
+    leaderboard.insert(index, result);
+}
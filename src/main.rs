@@ -29,9 +29,9 @@ extern crate sdl2;
 extern crate sdl2_window;
 
 //internal
-//Beatmaps mod. Was renamed due to name collision in external and internal names
-mod map;
-use map::*;
+//Beatmap format, curves and difficulty, shared with tools
+extern crate osu_format;
+use osu_format::*;
 
 mod game;
 use game::*;
@@ -80,7 +80,13 @@ fn main() {
 
                 Event::Update(_) => {
                     //Game loop logics should be placed there
-                    let title = format!("osu-rs @ {}FPS @ {:?}", fps, Game.borrow().app.current_state);
+                    let game = Game.borrow();
+                    let mut title = format!("osu-rs @ {}FPS @ {:?}", fps, game.app.current_state);
+                    if game.app.current_state == GameState::PlayResult {
+                        if let Some(ref result) = game.last_result {
+                            title = format!("{} @ {}", title, result.summary());
+                        }
+                    }
                     window.borrow_mut().set_title(title);
                 }
                 _ => {}
@@ -1,6 +0,0 @@
-pub mod bezier;
-pub mod hit_object;
-pub mod beatmap;
-
-pub use map::hit_object::*;
-pub use map::beatmap::*;